@@ -0,0 +1,104 @@
+use anyhow::{anyhow, Result};
+use std::str::FromStr;
+
+/// How the values that fall into a column of the sparkline are combined into its height.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aggregate {
+    Sum,
+    Avg,
+    Min,
+    Max,
+    /// The value that this percentage of the others are at or below, like 99 for p99.
+    Percentile(f64),
+}
+
+impl Aggregate {
+    /// Combines `values`, which it may reorder. Returns `None` if there are none.
+    pub fn apply(&self, values: &mut [f64]) -> Option<f64> {
+        if values.is_empty() {
+            return None;
+        }
+        let sum = || values.iter().sum::<f64>();
+        Some(match self {
+            Aggregate::Sum => sum(),
+            Aggregate::Avg => sum() / values.len() as f64,
+            Aggregate::Min => values.iter().copied().fold(f64::INFINITY, f64::min),
+            Aggregate::Max => values.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            Aggregate::Percentile(percentile) => {
+                // The nearest rank, which is always one of the values
+                let rank = (percentile / 100.0 * values.len() as f64).ceil() as usize;
+                let index = rank.clamp(1, values.len()) - 1;
+                *values.select_nth_unstable_by(index, f64::total_cmp).1
+            }
+        })
+    }
+}
+
+impl FromStr for Aggregate {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            anyhow!(
+                "invalid aggregate '{}' (expected sum, avg, min, max, or a percentile like p99)",
+                s
+            )
+        };
+        match s {
+            "sum" => Ok(Aggregate::Sum),
+            "avg" | "mean" => Ok(Aggregate::Avg),
+            "min" => Ok(Aggregate::Min),
+            "max" => Ok(Aggregate::Max),
+            _ => {
+                let percentile: f64 = s
+                    .strip_prefix('p')
+                    .and_then(|percentile| percentile.parse().ok())
+                    .ok_or_else(invalid)?;
+                if percentile > 0.0 && percentile <= 100.0 {
+                    Ok(Aggregate::Percentile(percentile))
+                } else {
+                    Err(invalid())
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aggregate_from_str() {
+        assert_eq!("sum".parse::<Aggregate>().unwrap(), Aggregate::Sum);
+        assert_eq!("mean".parse::<Aggregate>().unwrap(), Aggregate::Avg);
+        assert_eq!(
+            "p99".parse::<Aggregate>().unwrap(),
+            Aggregate::Percentile(99.0)
+        );
+        assert_eq!(
+            "p99.9".parse::<Aggregate>().unwrap(),
+            Aggregate::Percentile(99.9)
+        );
+        assert!("p0".parse::<Aggregate>().is_err());
+        assert!("p101".parse::<Aggregate>().is_err());
+        assert!("median".parse::<Aggregate>().is_err());
+    }
+
+    #[test]
+    fn aggregate_apply() {
+        let apply = |aggregate: &str| {
+            let mut values = [5.0, 1.0, 4.0, 2.0, 3.0, 10.0, 6.0, 7.0, 9.0, 8.0];
+            aggregate.parse::<Aggregate>().unwrap().apply(&mut values)
+        };
+        assert_eq!(apply("sum"), Some(55.0));
+        assert_eq!(apply("avg"), Some(5.5));
+        assert_eq!(apply("min"), Some(1.0));
+        assert_eq!(apply("max"), Some(10.0));
+        assert_eq!(apply("p50"), Some(5.0));
+        assert_eq!(apply("p90"), Some(9.0));
+        assert_eq!(apply("p99"), Some(10.0));
+        assert_eq!(apply("p1"), Some(1.0));
+        assert_eq!(Aggregate::Sum.apply(&mut []), None);
+    }
+}
@@ -1,13 +1,45 @@
-use anyhow::{anyhow, Result};
-use clap::{Arg, Command};
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::{parser::ValueSource, Arg, Command};
 use rayon::prelude::*;
-use std::{fs, io::IsTerminal};
+use regex::Regex;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{BufRead, BufReader, IsTerminal, Read, Seek, SeekFrom, Write},
+    path::{Path, PathBuf},
+};
 use terminal_size::{terminal_size, Width};
 
 use file_chunker::FileChunker;
+use memmap2::Mmap;
+
+use krapslog::{
+    Aggregate, Compression, CsvColumn, Extractor, Field, Follower, LineFilter, Occurrence,
+    ScanResult, ScanStats, ScannedLine, SketchTimeline, TimeBound, TimeRange, TimeSpan,
+    TimestampFinder, Timezone,
+};
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
+/// Value of `--format` that asks for the timestamp format to be detected.
+const AUTO_FORMAT: &str = "auto";
+
+/// Number of lines at the start of the log that are used to detect the timestamp format.
+const DETECTION_SAMPLE_LINES: usize = 1000;
+
+/// Narrowest sparkline that `--per-file` draws, however long the file names are.
+const MIN_SPARKLINE_WIDTH: usize = 20;
+
+/// Percentiles that `--bands` draws, from the top row down.
+const BAND_PERCENTILES: [f64; 3] = [99.0, 90.0, 50.0];
+
+/// Number of bytes at the end of the log that are searched for its last timestamp.
+const TAIL_SIZE: u64 = 64 * 1024;
+
+/// Size of the blocks of decompressed data that are handed to the scanning threads.
+const SCAN_BLOCK_SIZE: usize = 4 * 1024 * 1024;
+
 fn main() -> Result<()> {
     let num_cores = num_cpus::get_physical();
     let num_cores_for_display: &'static str = Box::leak(format!("{}", num_cores).into_boxed_str());
@@ -17,8 +49,9 @@ fn main() -> Result<()> {
         .disable_help_flag(true)
         .arg(
             Arg::new("FILE")
-                .help("Log file to visualize")
+                .help("Log files to visualize, or glob patterns that match them. Their timestamps are combined on one time axis. Reads standard input if none are given.")
                 .required(false)
+                .num_args(1..)
                 .index(1),
         )
         .arg(
@@ -42,10 +75,217 @@ fn main() -> Result<()> {
             Arg::new("FORMAT")
                 .short('F')
                 .long("format")
-                .help("Timestamp format to match")
+                .help("Timestamp format to match: a strftime-style format, a preset name (see --list-formats), or \"auto\" to detect it from the start of the log. Repeat it for logs that mix formats; they're tried in order on each line.")
                 .required(false)
+                .action(clap::ArgAction::Append)
                 .default_value("%d/%b/%Y:%H:%M:%S%.f"),
         )
+        .arg(
+            Arg::new("LIST_FORMATS")
+                .long("list-formats")
+                .help("List the preset timestamp formats and exit")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("OCCURRENCE")
+                .long("occurrence")
+                .value_name("first|last|N")
+                .help("Which timestamp to use on lines that contain several: the first, the last, or the Nth one")
+                .required(false)
+                .value_parser(clap::value_parser!(Occurrence))
+                .default_value("first"),
+        )
+        .arg(
+            Arg::new("ANCHORED")
+                .long("anchored")
+                .help("Only use timestamps at the very start of the line")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("JSON_FIELD")
+                .long("json-field")
+                .help("Read timestamps from this field of lines that are JSON objects: a field name (e.g. ts) or a JSON pointer (e.g. /meta/time). Numbers are read as UNIX timestamps. Implies --format auto unless a format is given.")
+                .required(false),
+        )
+        .arg(
+            Arg::new("LOGFMT")
+                .long("logfmt")
+                .value_name("KEY")
+                .help("Read timestamps from this key of logfmt lines (e.g. level=info ts=2024-01-02T03:04:05Z). Without a key, the first of ts, time and t is used. The key must follow an equals sign, as in --logfmt=ts. Implies --format auto unless a format is given.")
+                .required(false)
+                .num_args(0..=1)
+                .require_equals(true)
+                .conflicts_with("JSON_FIELD"),
+        )
+        .arg(
+            Arg::new("CSV_COLUMN")
+                .long("csv-column")
+                .value_name("N|NAME")
+                .help("Read timestamps from this column of CSV lines: a position starting at 1, or a name from the header on the first line. Implies --format auto unless a format is given.")
+                .required(false)
+                .value_parser(clap::value_parser!(CsvColumn))
+                .conflicts_with_all(["JSON_FIELD", "LOGFMT"]),
+        )
+        .arg(
+            Arg::new("DELIMITER")
+                .long("delimiter")
+                .help("Delimiter between CSV columns, e.g. ';' or 'tab' for TSV")
+                .required(false)
+                .value_parser(parse_delimiter)
+                .default_value(",")
+                .requires("CSV_COLUMN"),
+        )
+        .arg(
+            Arg::new("TIMESTAMP_REGEX")
+                .long("timestamp-regex")
+                .value_name("REGEX")
+                .help("Read timestamps from the part of each line that the capture group named ts matches, e.g. '\\[(?P<ts>[^\\]]+)\\]'. Implies --format auto unless a format is given.")
+                .required(false)
+                .value_parser(|s: &str| Extractor::timestamp_regex(s))
+                .conflicts_with_all(["JSON_FIELD", "LOGFMT", "CSV_COLUMN"]),
+        )
+        .arg(
+            Arg::new("TIMEZONE")
+                .short('z')
+                .long("timezone")
+                .help("Timezone of timestamps that don't include a UTC offset: an IANA name (e.g. Europe/Berlin), an offset (e.g. +02:00), UTC, or local")
+                .required(false)
+                .value_parser(clap::value_parser!(Timezone))
+                .default_value("UTC"),
+        )
+        .arg(
+            Arg::new("YEAR")
+                .long("year")
+                .help("Year that the log ends in, for timestamps that don't include one, like in RFC 3164 syslog (defaults to the year of the file's last modification, or the current year for standard input). Lines from later in the year than the last line are put in the year before.")
+                .required(false)
+                .value_parser(clap::value_parser!(i32).range(1..=9999)),
+        )
+        .arg(
+            Arg::new("BUCKET")
+                .long("bucket")
+                .value_name("DURATION")
+                .help("Make each column of the sparkline cover this much time, e.g. 1m, 5m or 1h, starting on the minute or the hour in --timezone. If there are more columns than fit, the latest ones are drawn.")
+                .required(false)
+                .value_parser(clap::value_parser!(TimeSpan)),
+        )
+        .arg(
+            Arg::new("MATCH")
+                .long("match")
+                .value_name("REGEX")
+                .help("Only use lines that match this regex. Repeat it to use lines that match any of several.")
+                .required(false)
+                .value_parser(|s: &str| Regex::new(s).map(|_| s.to_string()))
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("EXCLUDE")
+                .long("exclude")
+                .value_name("REGEX")
+                .help("Leave out lines that match this regex. Repeat it to leave out lines that match any of several.")
+                .required(false)
+                .value_parser(|s: &str| Regex::new(s).map(|_| s.to_string()))
+                .action(clap::ArgAction::Append),
+        )
+        .arg(
+            Arg::new("SINCE")
+                .long("since")
+                .value_name("TIME")
+                .help("Leave out timestamps before this time: a date and time (e.g. \"2024-01-02 03:04\" in the --timezone, or 2024-01-02T03:04:05Z), or a time relative to now (e.g. \"2h ago\")")
+                .required(false)
+                .value_parser(clap::value_parser!(TimeBound)),
+        )
+        .arg(
+            Arg::new("UNTIL")
+                .long("until")
+                .value_name("TIME")
+                .help("Leave out timestamps after this time, given like --since")
+                .required(false)
+                .value_parser(clap::value_parser!(TimeBound)),
+        )
+        .arg(
+            Arg::new("STATS")
+                .long("stats")
+                .help("Print statistics about the scan to standard error, such as how many lines had a timestamp")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("PER_FILE")
+                .long("per-file")
+                .help("Draw a sparkline for each file, labelled with its name and number of timestamps. They share a time axis and scale so that the files can be compared.")
+                .action(clap::ArgAction::SetTrue)
+                .requires("FILE"),
+        )
+        .arg(
+            Arg::new("GROUP_BY")
+                .long("group-by")
+                .value_name("REGEX")
+                .help("Draw a sparkline for each distinct value that this regex captures, like ' ([1-5])[0-9][0-9] ' for HTTP status classes, under one for all lines. The value is what the first capture group that participates in the match captures, or the whole match if none does.")
+                .required(false)
+                .value_parser(|s: &str| Regex::new(s))
+                .conflicts_with("PER_FILE"),
+        )
+        .arg(
+            Arg::new("TOP")
+                .long("top")
+                .value_name("N")
+                .help("Number of groups that --group-by draws, starting with the largest")
+                .value_parser(clap::value_parser!(u64).range(1..))
+                .default_value("10")
+                .requires("GROUP_BY"),
+        )
+        .arg(
+            Arg::new("VALUE")
+                .long("value")
+                .value_name("REGEX")
+                .help("Plot a number from each line instead of counting lines: the first capture group of this regex, like 'took (\\d+)ms' for a response time. The numbers in each column are combined with --aggregate.")
+                .required(false)
+                .value_parser(|s: &str| Regex::new(s))
+                .conflicts_with("GROUP_BY"),
+        )
+        .arg(
+            Arg::new("AGGREGATE")
+                .long("aggregate")
+                .value_name("sum|avg|min|max|pN")
+                .help("How --value combines the numbers in each column: their sum, average, minimum, maximum, or a percentile like p99")
+                .value_parser(clap::value_parser!(Aggregate))
+                .default_value("avg")
+                .requires("VALUE"),
+        )
+        .arg(
+            Arg::new("BANDS")
+                .long("bands")
+                .help("Draw the 99th, 90th and 50th percentile of the --value numbers in each column as three sparklines on a shared scale, so that the tail can be compared with the middle")
+                .action(clap::ArgAction::SetTrue)
+                .requires("VALUE")
+                .conflicts_with_all(["AGGREGATE", "PER_FILE"]),
+        )
+        .arg(
+            Arg::new("FOLLOW")
+                .short('f')
+                .long("follow")
+                .help("Keep reading lines as they're appended to the files, like tail -F, and redraw the sparkline over the last --window of time")
+                .action(clap::ArgAction::SetTrue)
+                .requires("FILE")
+                .conflicts_with_all(["STATS", "GROUP_BY"]),
+        )
+        .arg(
+            Arg::new("WINDOW")
+                .long("window")
+                .value_name("DURATION")
+                .help("How far back in time --follow shows, e.g. 30s, 15m or 1h30m")
+                .value_parser(clap::value_parser!(TimeSpan))
+                .default_value("15m")
+                .requires("FOLLOW"),
+        )
+        .arg(
+            Arg::new("INTERVAL")
+                .long("interval")
+                .value_name("DURATION")
+                .help("How often --follow redraws the sparkline")
+                .value_parser(clap::value_parser!(TimeSpan))
+                .default_value("2s")
+                .requires("FOLLOW"),
+        )
         .arg(
             Arg::new("CONCURRENCY")
                 .short('c')
@@ -63,49 +303,1096 @@ fn main() -> Result<()> {
         );
     let arg_matches = cmd.get_matches();
 
-    let timestamp_format: &String = arg_matches.get_one::<String>("FORMAT").unwrap();
-    let timestamps = match arg_matches.get_one::<String>("FILE") {
-        None => {
-            if std::io::stdin().is_terminal() {
-                eprintln!("Reading from standard input. Paste your log and then send EOF (e.g. by pressing ctrl-D).");
-            }
+    if arg_matches.get_flag("LIST_FORMATS") {
+        print_presets();
+        return Ok(());
+    }
 
-            krapslog::scan_for_timestamps(std::io::stdin(), timestamp_format)
+    let extractor = if let Some(field) = arg_matches.get_one::<String>("JSON_FIELD") {
+        Extractor::json_field(field)
+    } else if let Some(column) = arg_matches.get_one::<CsvColumn>("CSV_COLUMN") {
+        Extractor::csv_column(column.clone(), *arg_matches.get_one("DELIMITER").unwrap())
+    } else if let Some(extractor) = arg_matches.get_one::<Extractor>("TIMESTAMP_REGEX") {
+        extractor.clone()
+    } else if arg_matches.contains_id("LOGFMT") {
+        Extractor::logfmt_key(arg_matches.get_one::<String>("LOGFMT").map(String::as_str))
+    } else {
+        Extractor::Line
+    };
+    let timestamp_formats: Vec<String> = match arg_matches.value_source("FORMAT") {
+        // Structured logs rarely use the default format, so look for whichever one they use
+        Some(ValueSource::DefaultValue) if extractor != Extractor::Line => {
+            vec![AUTO_FORMAT.to_string()]
         }
-        Some(filename) => {
-            let file = fs::File::open(filename)?;
-            let chunker = FileChunker::new(&file)?;
-            let mut count = *arg_matches.get_one("CONCURRENCY").unwrap();
-            if file.metadata()?.len() < 10 * 1024 * 1024 {
-                count = 1;
-            }
+        _ => arg_matches
+            .get_many::<String>("FORMAT")
+            .unwrap()
+            .cloned()
+            .collect(),
+    };
+    if timestamp_formats
+        .iter()
+        .filter(|format| *format == AUTO_FORMAT)
+        .count()
+        > 1
+    {
+        return Err(anyhow!(
+            "--format auto can only be given once, since only one format is detected"
+        ));
+    }
+    let timezone: Timezone = *arg_matches.get_one("TIMEZONE").unwrap();
+    let now = Utc::now();
+    let resolve = |id: &str| -> Result<Option<i64>> {
+        arg_matches
+            .get_one::<TimeBound>(id)
+            .map(|bound| {
+                bound.resolve(now, &timezone).ok_or_else(|| {
+                    anyhow!("The time given for --{} is out of range", id.to_lowercase())
+                })
+            })
+            .transpose()
+    };
+    let range = TimeRange {
+        since: resolve("SINCE")?,
+        until: resolve("UNTIL")?,
+    };
+    if let (Some(since), Some(until)) = (range.since, range.until) {
+        if since > until {
+            return Err(anyhow!(
+                "The time given for --since is after the one for --until"
+            ));
+        }
+    }
+    let patterns = |id: &str| -> Vec<String> {
+        arg_matches
+            .get_many::<String>(id)
+            .map_or(vec![], |patterns| patterns.cloned().collect())
+    };
+    let filter = LineFilter::new(&patterns("MATCH"), &patterns("EXCLUDE"))?;
+    let mut options = ScanOptions {
+        formats: timestamp_formats,
+        timezone,
+        year: arg_matches.get_one("YEAR").copied(),
+        extractor,
+        occurrence: *arg_matches.get_one("OCCURRENCE").unwrap(),
+        anchored: arg_matches.get_flag("ANCHORED"),
+        range,
+        filter,
+        group_by: arg_matches.get_one::<Regex>("GROUP_BY").cloned(),
+        value: arg_matches.get_one::<Regex>("VALUE").cloned(),
+        sketch_values: arg_matches.get_flag("BANDS"),
+    };
+    let detect_format = options.detects_format();
+    let paths = match arg_matches.get_many::<String>("FILE") {
+        Some(args) => expand_paths(args)?,
+        None => vec![],
+    };
+    let view = View {
+        markers: *arg_matches.get_one("MARKERS").unwrap(),
+        height: std::cmp::max(1, *arg_matches.get_one("HEIGHT").unwrap()),
+        rows: match (
+            arg_matches.get_flag("PER_FILE"),
+            arg_matches.contains_id("GROUP_BY"),
+            arg_matches.get_flag("BANDS"),
+        ) {
+            (true, _, _) => Rows::PerFile,
+            (_, true, _) => Rows::PerGroup,
+            (_, _, true) => Rows::Bands,
+            _ => Rows::Combined,
+        },
+        bucket: arg_matches.get_one("BUCKET").copied(),
+        timezone,
+        aggregate: if arg_matches.contains_id("VALUE") {
+            arg_matches.get_one("AGGREGATE").copied()
+        } else {
+            None
+        },
+    };
+    if arg_matches.get_flag("FOLLOW") {
+        let window = *arg_matches.get_one("WINDOW").unwrap();
+        let interval = *arg_matches.get_one("INTERVAL").unwrap();
+        return follow(paths, &mut options, &view, window, interval);
+    }
+    let paths_count = paths.len();
+    let results: Vec<(String, ScanResult)> = if paths.is_empty() {
+        if std::io::stdin().is_terminal() {
+            eprintln!("Reading from standard input. Paste your log and then send EOF (e.g. by pressing ctrl-D).");
+        }
+
+        let header = read_header(std::io::stdin())?;
+        let compression = Compression::detect(&header);
+        let mut stdin = BufReader::new(compression.decoder(header.chain(std::io::stdin()))?);
+        let sample = if options.needs_sample() {
+            read_sample(&mut stdin)?
+        } else {
+            vec![]
+        };
+        options.detect_formats(&sample)?;
+        let date_finder = options.date_finder(Utc::now(), &sample, || Ok(vec![]))?;
+        let result = krapslog::scan_for_timestamps_with(sample.chain(stdin), &date_finder)?;
+        vec![(String::from("stdin"), result)]
+    } else {
+        let files = paths
+            .into_iter()
+            .map(LogFile::open)
+            .collect::<Result<Vec<_>>>()?;
+        // The format is detected once, from the first file, so that every file is read alike
+        if options.detects_format() {
+            options.detect_formats(&files[0].read_sample()?)?;
+        }
+        let concurrency = *arg_matches.get_one("CONCURRENCY").unwrap();
+        let results = files.par_iter().map(|file| {
+            let sample = if options.extractor.needs_header() {
+                file.read_sample()?
+            } else {
+                vec![]
+            };
+            let date_finder =
+                options.date_finder(file.last_written()?, &sample, || file.read_tail())?;
+            let result = file
+                .scan(&date_finder, concurrency)
+                .with_context(|| format!("Couldn't read {}", file.path.display()))?;
+            Ok((file.path.display().to_string(), result))
+        });
+        if view.rows == Rows::PerFile {
+            results.collect::<Result<_>>()?
+        } else {
+            let result = results
+                .map(|result| result.map(|(_, result)| result))
+                .try_reduce(ScanResult::default, |a, b| Ok(a.merge(b)))?;
+            vec![(String::new(), result)]
+        }
+    };
+
+    let mut stats = ScanStats::default();
+    let mut sources = vec![];
+    let mut groups = HashMap::new();
+    for (name, result) in results {
+        stats = stats.merge(result.stats);
+        sources.push(Source {
+            name,
+            timestamps: result.timestamps,
+            values: result.values,
+            value_sketches: result.value_sketches,
+        });
+        groups.extend(result.groups);
+    }
+    if arg_matches.get_flag("STATS") {
+        print_stats(&stats);
+    } else {
+        if stats.malformed_lines > 0 {
+            eprintln!("Skipped malformed lines: {}", stats.malformed_lines);
+        }
+        if stats.invalid_utf8_lines > 0 {
+            eprintln!("Lines with invalid UTF-8: {}", stats.invalid_utf8_lines);
+        }
+    }
+
+    if stats.matched_lines == 0 {
+        if stats.out_of_range_lines > 0 {
+            return Err(anyhow!(
+                "Found no timestamps between --since and --until ({} were outside of them)",
+                stats.out_of_range_lines
+            ));
+        }
+        if detect_format {
+            return Err(anyhow!("Found no lines with a matching timestamp"));
+        }
+        return Err(anyhow!(
+            "Found no lines with a matching timestamp (try --format {} to detect the format)",
+            AUTO_FORMAT
+        ));
+    }
+    let has_values =
+        |source: &Source| !source.values.is_empty() || !source.value_sketches.is_empty();
+    if view.aggregate.is_some() && !sources.iter().any(has_values) {
+        return Err(anyhow!(
+            "Found no numbers that --value matches on the {} lines with a timestamp",
+            stats.matched_lines
+        ));
+    }
 
-            Ok(chunker
-                .chunks(count, Some('\n'))?
-                .into_par_iter()
-                .map(|chunk| krapslog::scan_for_timestamps(chunk, timestamp_format))
-                .filter_map(Result::ok)
-                .collect::<Vec<Vec<i64>>>()
-                .concat())
+    if view.rows == Rows::PerGroup {
+        let top = *arg_matches.get_one::<u64>("TOP").unwrap() as usize;
+        let mut groups: Vec<(String, Vec<i64>)> = groups.into_iter().collect();
+        groups.sort_by(|(a_key, a), (b_key, b)| b.len().cmp(&a.len()).then(a_key.cmp(b_key)));
+        if groups.len() > top {
+            eprintln!("Showing the {} largest of {} groups", top, groups.len());
+            groups.truncate(top);
         }
-    }?;
+        sources[0].name = String::from("(all)");
+        sources.extend(groups.into_iter().map(|(name, timestamps)| Source {
+            timestamps,
+            ..Source::new(name)
+        }));
+    } else if view.rows == Rows::Combined && paths_count > 1 {
+        // Markers are taken from the timestamps in order, which files scanned in parallel lose
+        sources[0].timestamps.par_sort_unstable();
+    }
+    print!("{}", render(&view, &sources, None));
 
-    if timestamps.is_empty() {
-        return Err(anyhow!("Found no lines with a matching timestamp"));
+    Ok(())
+}
+
+/// How the sparklines are drawn, as chosen on the command line.
+struct View {
+    markers: usize,
+    height: usize,
+    rows: Rows,
+    /// The length of time that each column covers, if it's fixed
+    bucket: Option<TimeSpan>,
+    /// The timezone whose wall clock the buckets line up with and the time markers show
+    timezone: Timezone,
+    /// How the values of each column are combined, if values are plotted instead of lines counted
+    aggregate: Option<Aggregate>,
+}
+
+impl View {
+    /// Whether the time markers show the timestamps at even steps through the lines, as they do
+    /// for a plain sparkline, rather than the times at even steps along the axis. That's only the
+    /// case when lines are counted over the span of their timestamps.
+    fn marks_timestamps(&self, range: Option<(i64, i64)>) -> bool {
+        range.is_none() && self.bucket.is_none() && self.aggregate.is_none()
     }
+}
+
+/// The timestamps found in a file, a group, or all of them, which are drawn as a sparkline.
+struct Source {
+    name: String,
+    timestamps: Vec<i64>,
+    /// The values read with `--value`, along with the timestamps of their lines
+    values: Vec<(i64, f64)>,
+    /// The same values, counted in sketches instead for `--bands`
+    value_sketches: SketchTimeline,
+}
 
+impl Source {
+    fn new(name: String) -> Source {
+        Source {
+            name,
+            timestamps: vec![],
+            values: vec![],
+            value_sketches: SketchTimeline::new(),
+        }
+    }
+
+    /// Puts the timestamps and values of several sources together, in order.
+    fn combine(sources: &[Source]) -> Source {
+        let mut combined = Source::new(String::new());
+        for source in sources {
+            combined.timestamps.extend(&source.timestamps);
+            combined.values.extend(&source.values);
+            combined.value_sketches = combined.value_sketches.merge(source.value_sketches.clone());
+        }
+        combined.timestamps.par_sort_unstable();
+        combined
+    }
+}
+
+/// Which sparklines are drawn.
+#[derive(Clone, Copy, PartialEq)]
+enum Rows {
+    /// One for all the timestamps
+    Combined,
+    /// One for each file, on a shared vertical scale so that the files can be compared
+    PerFile,
+    /// One for all the timestamps and one for each group. Each is scaled to its own peak, so that
+    /// the shape of a small group shows next to the others.
+    PerGroup,
+    /// One for each of the `BAND_PERCENTILES` of the values of all the sources, on a shared scale
+    Bands,
+}
+
+/// Draws a sparkline for each source, or one for all of them together. The time axis covers
+/// `range` if it's given, or else the span of the timestamps.
+fn render(view: &View, sources: &[Source], range: Option<(i64, i64)>) -> String {
     let terminal_width = match terminal_size() {
         Some((Width(w), _)) => w as usize,
         _ => 80,
     };
+    if view.rows != Rows::Combined {
+        return render_labelled(view, sources, range, terminal_width);
+    }
 
-    let num_markers: usize = *arg_matches.get_one("MARKERS").unwrap();
-    let num_lines: usize = std::cmp::max(1, *arg_matches.get_one("HEIGHT").unwrap());
-    let (header, footer) = krapslog::build_time_markers(&timestamps, num_markers, terminal_width);
-    let sparkline = krapslog::build_sparkline(&timestamps, terminal_width, num_lines);
-    print!("{}", header);
-    println!("{}", sparkline);
-    print!("{}", footer);
+    let combined: Source;
+    let source = match sources {
+        [source] => source,
+        _ => {
+            combined = Source::combine(sources);
+            &combined
+        }
+    };
+    if view.marks_timestamps(range) {
+        let timestamps = &source.timestamps;
+        let (header, footer) =
+            krapslog::build_time_markers(timestamps, view.markers, terminal_width, view.timezone);
+        let sparkline = krapslog::build_sparkline(timestamps, terminal_width, view.height);
+        return format!("{}{}\n{}", header, sparkline, footer);
+    }
 
-    Ok(())
+    let axis = Axis::new(view, range.unwrap_or_else(|| span(sources)), terminal_width);
+    let (header, footer) = axis.time_markers(view);
+    let sparkline = axis.sparklines(view, &[source]).remove(0);
+    format!("{}{}\n{}", header, sparkline, footer)
+}
+
+/// Finds the earliest and the latest timestamp of all the sources.
+fn span(sources: &[Source]) -> (i64, i64) {
+    let all_timestamps = sources.iter().flat_map(|source| &source.timestamps);
+    (
+        *all_timestamps.clone().min().unwrap(),
+        *all_timestamps.max().unwrap(),
+    )
+}
+
+/// The time axis of the sparklines: the span of time that they cover, split up into columns.
+struct Axis {
+    start: i64,
+    end: i64,
+    columns: usize,
+    bucket_size: Option<i64>,
+}
+
+impl Axis {
+    /// Spreads `range` over `width` columns. With `--bucket`, each column covers one bucket
+    /// instead, and only the latest buckets are kept if they don't all fit.
+    fn new(view: &View, (start, end): (i64, i64), width: usize) -> Axis {
+        let Some(bucket) = view.bucket else {
+            return Axis {
+                start,
+                end,
+                columns: width,
+                bucket_size: None,
+            };
+        };
+        let bucket_size = bucket.as_nanos();
+        let last_bucket = krapslog::bucket_start(end, bucket_size, view.timezone);
+        let first_bucket = krapslog::bucket_start(start, bucket_size, view.timezone);
+        let bucket_count = (last_bucket - first_bucket) / bucket_size;
+        let columns = (bucket_count as usize).saturating_add(1).min(width.max(1));
+        Axis {
+            start: last_bucket - (columns as i64 - 1) * bucket_size,
+            end: last_bucket,
+            columns,
+            bucket_size: Some(bucket_size),
+        }
+    }
+
+    /// Draws the sparklines of the sources, which either count their lines or aggregate their
+    /// values.
+    fn sparklines(&self, view: &View, sources: &[&Source]) -> Vec<String> {
+        let range = (self.start, self.end);
+        let Some(aggregate) = view.aggregate else {
+            let logs: Vec<&[i64]> = sources.iter().map(|s| s.timestamps.as_slice()).collect();
+            return match self.bucket_size {
+                Some(bucket_size) => krapslog::build_sparklines_in_buckets(
+                    &logs,
+                    self.start,
+                    self.columns,
+                    bucket_size,
+                    view.height,
+                ),
+                None => {
+                    krapslog::build_sparklines_in_range(&logs, range, self.columns, view.height)
+                }
+            };
+        };
+        let logs: Vec<&[(i64, f64)]> = sources.iter().map(|s| s.values.as_slice()).collect();
+        match self.bucket_size {
+            Some(bucket_size) => krapslog::build_value_sparklines_in_buckets(
+                &logs,
+                aggregate,
+                self.start,
+                self.columns,
+                bucket_size,
+                view.height,
+            ),
+            None => krapslog::build_value_sparklines_in_range(
+                &logs,
+                aggregate,
+                range,
+                self.columns,
+                view.height,
+            ),
+        }
+    }
+
+    /// Draws a sparkline for each of the `BAND_PERCENTILES` of the source's values.
+    fn bands(&self, view: &View, source: &Source) -> Vec<String> {
+        match self.bucket_size {
+            Some(bucket_size) => krapslog::build_percentile_sparklines_in_buckets(
+                &source.value_sketches,
+                &BAND_PERCENTILES,
+                self.start,
+                self.columns,
+                bucket_size,
+                view.height,
+            ),
+            None => krapslog::build_percentile_sparklines_in_range(
+                &source.value_sketches,
+                &BAND_PERCENTILES,
+                (self.start, self.end),
+                self.columns,
+                view.height,
+            ),
+        }
+    }
+
+    /// Builds the time markers, spread evenly over the axis rather than over the timestamps. With
+    /// buckets, each marker shows when the bucket in its column starts. A narrow axis, like one of
+    /// a few buckets, has fewer markers than asked for, or none.
+    fn time_markers(&self, view: &View) -> (String, String) {
+        if self.columns < 2 {
+            return (String::new(), String::new());
+        }
+        let columns = self.columns as i64;
+        let evenly_spaced: Vec<i64> = (0..columns)
+            .map(|column| self.start + (self.end - self.start) / (columns - 1) * column)
+            .collect();
+        krapslog::build_time_markers(&evenly_spaced, view.markers, self.columns, view.timezone)
+    }
+}
+
+/// Draws a sparkline for each source, labelled with its name and number of timestamps, or one for
+/// each percentile band, labelled with the percentile. They share a time axis so that they can be
+/// compared.
+fn render_labelled(
+    view: &View,
+    sources: &[Source],
+    range: Option<(i64, i64)>,
+    terminal_width: usize,
+) -> String {
+    let labels: Vec<String> = match view.rows {
+        Rows::Bands => BAND_PERCENTILES
+            .iter()
+            .map(|percentile| format!("p{}", percentile))
+            .collect(),
+        _ => {
+            let counts: Vec<String> = sources
+                .iter()
+                .map(|source| source.timestamps.len().to_string())
+                .collect();
+            let name_width = sources
+                .iter()
+                .map(|source| source.name.chars().count())
+                .max()
+                .unwrap();
+            let count_width = counts.iter().map(String::len).max().unwrap();
+            sources
+                .iter()
+                .zip(counts)
+                .map(|(source, count)| {
+                    format!("{:name_width$} {:>count_width$}", source.name, count)
+                })
+                .collect()
+        }
+    };
+    let label_width = labels
+        .iter()
+        .map(|label| label.chars().count())
+        .max()
+        .unwrap()
+        + 1;
+    let sparkline_width = terminal_width
+        .saturating_sub(label_width)
+        .max(MIN_SPARKLINE_WIDTH);
+
+    let axis = Axis::new(
+        view,
+        range.unwrap_or_else(|| span(sources)),
+        sparkline_width,
+    );
+    let (header, footer) = if view.marks_timestamps(range) {
+        // The first source of a group view holds all of the timestamps
+        let all = match view.rows {
+            Rows::PerGroup => Source::combine(&sources[..1]),
+            _ => Source::combine(sources),
+        };
+        krapslog::build_time_markers(
+            &all.timestamps,
+            view.markers,
+            sparkline_width,
+            view.timezone,
+        )
+    } else {
+        axis.time_markers(view)
+    };
+    let indent = |markers: String| {
+        markers
+            .lines()
+            .map(|line| format!("{:label_width$}{}\n", "", line))
+            .collect::<String>()
+    };
+
+    let sparklines = match view.rows {
+        Rows::PerGroup => sources
+            .iter()
+            .flat_map(|source| axis.sparklines(view, &[source]))
+            .collect(),
+        Rows::Bands => match sources {
+            [source] => axis.bands(view, source),
+            _ => axis.bands(view, &Source::combine(sources)),
+        },
+        _ => axis.sparklines(view, &sources.iter().collect::<Vec<_>>()),
+    };
+    let mut output = indent(header);
+    for (label, sparkline) in labels.iter().zip(sparklines) {
+        // The label goes on the bottom row, next to the base of the sparkline
+        let rows: Vec<&str> = sparkline.lines().collect();
+        for (row, line) in rows.iter().enumerate() {
+            let label = if row + 1 == rows.len() {
+                label.as_str()
+            } else {
+                ""
+            };
+            let label = format!("{:label_width$}", label);
+            output.push_str(&format!("{}{}\n", label, line));
+        }
+    }
+    output.push_str(&indent(footer));
+    output
+}
+
+/// Keeps reading the lines that are appended to the files, like `tail -F`, and redraws the
+/// sparkline in place every `interval`. It covers the last `window` of time. Runs until it's
+/// interrupted.
+fn follow(
+    paths: Vec<PathBuf>,
+    options: &mut ScanOptions,
+    view: &View,
+    window: TimeSpan,
+    interval: TimeSpan,
+) -> Result<()> {
+    let mut date_finders = vec![];
+    for (index, path) in paths.iter().enumerate() {
+        // Files that don't exist yet are waited for, unless they're needed to set things up
+        if !options.needs_sample() && !path.exists() {
+            date_finders.push(options.date_finder(Utc::now(), &[], || Ok(vec![]))?);
+            continue;
+        }
+        let file = LogFile::open(path.clone())?;
+        if file.compression != Compression::None {
+            return Err(anyhow!(
+                "Can't follow {} because it's compressed",
+                path.display()
+            ));
+        }
+        let sample = if options.needs_sample() {
+            file.read_sample()?
+        } else {
+            vec![]
+        };
+        if index == 0 {
+            options.detect_formats(&sample)?;
+        }
+        date_finders.push(options.date_finder(Utc::now(), &sample, || Ok(vec![]))?);
+    }
+    let mut followers: Vec<Follower> = paths.iter().map(Follower::new).collect();
+    let mut sources: Vec<Source> = paths
+        .iter()
+        .map(|path| Source::new(path.display().to_string()))
+        .collect();
+
+    // The window ends at the latest timestamp rather than at the current time, so that logs that
+    // are written with a delay, or whose timezone is off, still show up
+    let mut latest: Option<i64> = None;
+    let mut drawn_lines = 0;
+    loop {
+        for ((follower, date_finder), source) in
+            followers.iter_mut().zip(&date_finders).zip(&mut sources)
+        {
+            loop {
+                let lines = follower.read_lines(SCAN_BLOCK_SIZE)?;
+                if lines.is_empty() {
+                    break;
+                }
+                let result = krapslog::scan_for_timestamps_with(lines.as_slice(), date_finder)?;
+                latest = latest.max(result.timestamps.iter().copied().max());
+                source.timestamps.extend(result.timestamps);
+                source.values.extend(result.values);
+                source.value_sketches =
+                    std::mem::take(&mut source.value_sketches).merge(result.value_sketches);
+            }
+        }
+        let end = latest.unwrap_or_else(|| Utc::now().timestamp_nanos_opt().unwrap_or(i64::MAX));
+        let start = end - window.as_nanos();
+        for source in &mut sources {
+            source.timestamps.retain(|timestamp| *timestamp >= start);
+            source.timestamps.sort_unstable();
+            source.values.retain(|(timestamp, _)| *timestamp >= start);
+            source.value_sketches.retain_since(start);
+        }
+
+        let count: usize = sources.iter().map(|source| source.timestamps.len()).sum();
+        let frame = format!(
+            "{}Last {} up to {}: {} lines\n",
+            render(view, &sources, Some((start, end))),
+            window,
+            DateTime::from_timestamp_nanos(end + view.timezone.utc_offset(end))
+                .format("%Y-%m-%d %H:%M:%S"),
+            count
+        );
+        // Move back up to the previous frame and clear it before drawing over it
+        if drawn_lines > 0 {
+            print!("\x1b[{}A\x1b[J", drawn_lines);
+        }
+        print!("{}", frame);
+        std::io::stdout().flush()?;
+        drawn_lines = frame.lines().count();
+
+        std::thread::sleep(interval.as_std());
+    }
+}
+
+/// Scans a compressed log. Data that consists of independent members, like bgzip blocks or zstd
+/// frames, is split up and decompressed on several threads. Otherwise a single thread decompresses
+/// it and hands out blocks of lines to the others.
+fn scan_compressed(
+    data: &[u8],
+    compression: Compression,
+    concurrency: usize,
+    date_finder: &TimestampFinder,
+) -> Result<ScanResult> {
+    let ranges = compression.split(data, concurrency);
+    if ranges.len() == 1 {
+        return scan_in_blocks(compression.decoder(data)?, date_finder);
+    }
+
+    ranges
+        .par_iter()
+        .enumerate()
+        .map(|(index, range)| {
+            // A line that crosses into the next range belongs to this one. It may be longer than
+            // the next range, so its end is looked for in all of the data that follows.
+            let mut reader = BufReader::new(compression.decoder(&data[range.clone()])?);
+            if index > 0 {
+                let mut head = vec![];
+                reader.read_until(b'\n', &mut head)?;
+                if !head.ends_with(b"\n") {
+                    // The whole range is in the middle of a line that an earlier one scans
+                    return Ok(ScanResult::default());
+                }
+            }
+            let mut tail = vec![];
+            if range.end < data.len() {
+                BufReader::new(compression.decoder(&data[range.end..])?)
+                    .read_until(b'\n', &mut tail)?;
+            }
+            krapslog::scan_for_timestamps_with(reader.chain(tail.as_slice()), date_finder)
+        })
+        .try_reduce(ScanResult::default, |a, b| Ok(a.merge(b)))
+}
+
+/// Reads blocks of whole lines from `reader` and scans them in parallel.
+fn scan_in_blocks<R: Read + Send>(reader: R, date_finder: &TimestampFinder) -> Result<ScanResult> {
+    let mut reader = BufReader::new(reader);
+    let mut failed = false;
+    let blocks = std::iter::from_fn(|| {
+        if failed {
+            return None;
+        }
+        let mut block = Vec::with_capacity(SCAN_BLOCK_SIZE);
+        let read = (&mut reader)
+            .take(SCAN_BLOCK_SIZE as u64)
+            .read_to_end(&mut block)
+            .and_then(|_| reader.read_until(b'\n', &mut block));
+        match read {
+            Ok(_) if block.is_empty() => None,
+            Ok(_) => Some(Ok(block)),
+            Err(e) => {
+                failed = true;
+                Some(Err(e))
+            }
+        }
+    });
+
+    let mut results = blocks
+        .enumerate()
+        .par_bridge()
+        .map(|(index, block)| {
+            let result = krapslog::scan_for_timestamps_with(block?.as_slice(), date_finder)?;
+            Ok((index, result))
+        })
+        .collect::<Result<Vec<_>>>()?;
+    results.sort_by_key(|(index, _)| *index);
+    Ok(results
+        .into_iter()
+        .map(|(_, result)| result)
+        .fold(ScanResult::default(), ScanResult::merge))
+}
+
+/// Reads as much of the start of `reader` as `Compression::detect` needs. A single read might
+/// return less, e.g. from a pipe.
+fn read_header<R: Read>(reader: R) -> Result<Vec<u8>> {
+    let mut header = Vec::with_capacity(Compression::HEADER_LEN);
+    reader
+        .take(Compression::HEADER_LEN as u64)
+        .read_to_end(&mut header)?;
+    Ok(header)
+}
+
+/// Reads the first few lines of the log so that its timestamp format can be detected.
+fn read_sample<R: BufRead>(reader: &mut R) -> Result<Vec<u8>> {
+    let mut sample = vec![];
+    for _ in 0..DETECTION_SAMPLE_LINES {
+        if reader.read_until(b'\n', &mut sample)? == 0 {
+            break;
+        }
+    }
+    Ok(sample)
+}
+
+/// Picks the date that timestamps without a year are assumed not to be later than, before the last
+/// line of the log has been read: the end of `year` if it was given, or else the time that the log
+/// was last written to.
+fn reference_date(year: Option<i32>, last_written: DateTime<Utc>) -> NaiveDate {
+    match year {
+        Some(year) => NaiveDate::from_ymd_opt(year, 12, 31).expect("year is in range"),
+        None => last_written.date_naive(),
+    }
+}
+
+/// Finds the timestamp on the last line of `tail` that has one.
+fn last_timestamp(date_finder: &TimestampFinder, tail: &[u8]) -> Option<i64> {
+    String::from_utf8_lossy(tail)
+        .lines()
+        .rev()
+        .find_map(|line| match date_finder.scan_line(line) {
+            ScannedLine::Timestamp(timestamp) => Some(timestamp),
+            _ => None,
+        })
+}
+
+/// How each log is scanned, as chosen on the command line.
+struct ScanOptions {
+    formats: Vec<String>,
+    timezone: Timezone,
+    year: Option<i32>,
+    extractor: Extractor,
+    occurrence: Occurrence,
+    anchored: bool,
+    range: TimeRange,
+    filter: LineFilter,
+    group_by: Option<Regex>,
+    value: Option<Regex>,
+    /// Whether the values are counted in sketches for `--bands` rather than kept
+    sketch_values: bool,
+}
+
+impl ScanOptions {
+    fn detects_format(&self) -> bool {
+        self.formats.iter().any(|format| format == AUTO_FORMAT)
+    }
+
+    fn needs_sample(&self) -> bool {
+        self.detects_format() || self.extractor.needs_header()
+    }
+
+    /// Replaces "auto" among the formats with the format that's detected in `sample`.
+    fn detect_formats(&mut self, sample: &[u8]) -> Result<()> {
+        let Some(auto) = self
+            .formats
+            .iter_mut()
+            .find(|format| *format == AUTO_FORMAT)
+        else {
+            return Ok(());
+        };
+        let sample = String::from_utf8_lossy(sample);
+        let extractor = if self.extractor.needs_header() {
+            self.extractor
+                .clone()
+                .with_header(sample.lines().next().unwrap_or_default())?
+        } else {
+            self.extractor.clone()
+        };
+        let sample = extract_sample(&extractor, &sample);
+        let detected = krapslog::detect_timestamp_format(&sample).ok_or_else(|| {
+            anyhow!("Couldn't detect the timestamp format. Use --format to specify it.")
+        })?;
+        eprintln!(
+            "Detected timestamp format {} (\"{}\") in {} of {} sampled lines",
+            detected.name, detected.format, detected.matching_lines, detected.sampled_lines
+        );
+        *auto = detected.format.to_string();
+        Ok(())
+    }
+
+    /// Builds the finder for a log that was last written to at `last_written`. The CSV header, if
+    /// one is needed, is read from `sample`. Timestamps without a year are placed in the year of
+    /// the last one at the end of the log, or in the year before if they're from later in the year
+    /// than it, as happens when a log goes from December into January. The end of the log is only
+    /// read, with `read_tail`, if the formats leave out the year.
+    fn date_finder(
+        &self,
+        last_written: DateTime<Utc>,
+        sample: &[u8],
+        read_tail: impl FnOnce() -> Result<Vec<u8>>,
+    ) -> Result<TimestampFinder> {
+        let extractor = if self.extractor.needs_header() {
+            let sample = String::from_utf8_lossy(sample);
+            self.extractor
+                .clone()
+                .with_header(sample.lines().next().unwrap_or_default())?
+        } else {
+            self.extractor.clone()
+        };
+        let mut date_finder = TimestampFinder::from_formats(&self.formats)?
+            .with_timezone(self.timezone)
+            .with_reference_date(reference_date(self.year, last_written))
+            .with_extractor(extractor)
+            .with_occurrence(self.occurrence)
+            .with_anchor(self.anchored);
+        if date_finder.infers_year() {
+            if let Some(last_timestamp) = last_timestamp(&date_finder, &read_tail()?) {
+                let last_date = DateTime::from_timestamp_nanos(last_timestamp).date_naive();
+                date_finder = date_finder.with_reference_date(last_date);
+            }
+        }
+        let date_finder = date_finder
+            .with_range(self.range)
+            .with_filter(self.filter.clone());
+        let date_finder = match &self.group_by {
+            Some(regex) => date_finder.with_group_by(regex.clone()),
+            None => date_finder,
+        };
+        Ok(match &self.value {
+            Some(regex) => date_finder
+                .with_value(regex.clone())
+                .with_sketched_values(self.sketch_values),
+            None => date_finder,
+        })
+    }
+}
+
+/// A log file that's been opened and checked for compression.
+struct LogFile {
+    path: PathBuf,
+    file: fs::File,
+    compression: Compression,
+    // Only compressed files are mapped here; FileChunker maps the others itself
+    compressed: Option<Mmap>,
+}
+
+impl LogFile {
+    fn open(path: PathBuf) -> Result<Self> {
+        let open = || -> Result<Self> {
+            let file = fs::File::open(&path)?;
+            let compression = Compression::detect(&read_header(&file)?);
+            let compressed = match compression {
+                Compression::None => None,
+                // SAFETY: The mapping is only read from. If another process truncates the file
+                // while it's mapped, reading past the new end raises SIGBUS, the same risk that
+                // FileChunker takes with uncompressed files. Logs are appended to rather than
+                // rewritten in place, and compressed ones aren't usually written to at all.
+                _ => Some(unsafe { Mmap::map(&file)? }),
+            };
+            Ok(LogFile {
+                path: path.clone(),
+                file,
+                compression,
+                compressed,
+            })
+        };
+        open().with_context(|| format!("Couldn't open {}", path.display()))
+    }
+
+    /// Reads the first few lines of the file, after decompressing it.
+    fn read_sample(&self) -> Result<Vec<u8>> {
+        match &self.compressed {
+            None => read_sample(&mut BufReader::new(fs::File::open(&self.path)?)),
+            Some(data) => read_sample(&mut BufReader::new(self.compression.decoder(&data[..])?)),
+        }
+    }
+
+    /// Reads the last few lines of the file. Compressed files can't be read from the end, so
+    /// nothing is read from them.
+    fn read_tail(&self) -> Result<Vec<u8>> {
+        let mut tail = vec![];
+        if self.compressed.is_none() {
+            let mut file = &self.file;
+            let len = file.metadata()?.len();
+            file.seek(SeekFrom::Start(len.saturating_sub(TAIL_SIZE)))?;
+            file.read_to_end(&mut tail)?;
+        }
+        Ok(tail)
+    }
+
+    fn last_written(&self) -> Result<DateTime<Utc>> {
+        Ok(self.file.metadata()?.modified()?.into())
+    }
+
+    fn scan(&self, date_finder: &TimestampFinder, concurrency: usize) -> Result<ScanResult> {
+        if let Some(data) = &self.compressed {
+            return scan_compressed(data, self.compression, concurrency, date_finder);
+        }
+
+        let chunker = FileChunker::new(&self.file)?;
+        let count = if self.file.metadata()?.len() < 10 * 1024 * 1024 {
+            1
+        } else {
+            concurrency
+        };
+        chunker
+            .chunks(count, Some('\n'))?
+            .into_par_iter()
+            .map(|chunk| krapslog::scan_for_timestamps_with(chunk, date_finder))
+            .try_reduce(ScanResult::default, |a, b| Ok(a.merge(b)))
+    }
+}
+
+/// Expands glob patterns among the file arguments, for shells that pass them through unexpanded.
+/// Arguments that name an existing file are used as they are.
+fn expand_paths<'a>(args: impl Iterator<Item = &'a String>) -> Result<Vec<PathBuf>> {
+    let mut paths = vec![];
+    for arg in args {
+        let path = Path::new(arg);
+        if path.exists() || !arg.contains(['*', '?', '[']) {
+            paths.push(path.to_path_buf());
+            continue;
+        }
+        let matches = glob::glob(arg)?.collect::<Result<Vec<_>, _>>()?;
+        if matches.is_empty() {
+            return Err(anyhow!("No files match {}", arg));
+        }
+        paths.extend(matches);
+    }
+    Ok(paths)
+}
+
+/// Narrows each sampled line down to the part that the extractor selects, so that format detection
+/// only sees the timestamps.
+fn extract_sample(extractor: &Extractor, sample: &str) -> String {
+    sample
+        .lines()
+        .filter_map(|line| match extractor.extract(line) {
+            Field::Text(text) => Some(text.into_owned()),
+            Field::Number(number) => Some(number),
+            Field::Missing | Field::Malformed => None,
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Longest part of an unmatched line that `--stats` shows.
+const MAX_SAMPLE_LINE_CHARS: usize = 120;
+
+fn print_stats(stats: &ScanStats) {
+    let percentage = |count: usize| match stats.total_lines {
+        0 => 0.0,
+        total => 100.0 * count as f64 / total as f64,
+    };
+    let format_timestamp = |timestamp: Option<i64>| match timestamp {
+        Some(timestamp) => DateTime::from_timestamp_nanos(timestamp)
+            .format("%Y-%m-%d %H:%M:%S%.f UTC")
+            .to_string(),
+        None => String::from("-"),
+    };
+
+    eprintln!("Lines:                  {}", stats.total_lines);
+    eprintln!(
+        "With a timestamp:       {} ({:.1}%)",
+        stats.matched_lines,
+        percentage(stats.matched_lines)
+    );
+    eprintln!(
+        "Outside time range:     {} ({:.1}%)",
+        stats.out_of_range_lines,
+        percentage(stats.out_of_range_lines)
+    );
+    eprintln!(
+        "Filtered out:           {} ({:.1}%)",
+        stats.filtered_lines,
+        percentage(stats.filtered_lines)
+    );
+    eprintln!(
+        "Unparseable timestamp:  {} ({:.1}%)",
+        stats.unparseable_lines,
+        percentage(stats.unparseable_lines)
+    );
+    eprintln!(
+        "Malformed:              {} ({:.1}%)",
+        stats.malformed_lines,
+        percentage(stats.malformed_lines)
+    );
+    eprintln!(
+        "Invalid UTF-8:          {} ({:.1}%)",
+        stats.invalid_utf8_lines,
+        percentage(stats.invalid_utf8_lines)
+    );
+    if stats.missing_value_lines > 0 {
+        eprintln!(
+            "Without a value:        {} ({:.1}%)",
+            stats.missing_value_lines,
+            percentage(stats.missing_value_lines)
+        );
+    }
+    eprintln!(
+        "First timestamp:        {}",
+        format_timestamp(stats.first_timestamp)
+    );
+    eprintln!(
+        "Last timestamp:         {}",
+        format_timestamp(stats.last_timestamp)
+    );
+    eprintln!(
+        "Time span:              {}",
+        stats.time_span().map_or(String::from("-"), format_duration)
+    );
+    if !stats.unmatched_samples.is_empty() {
+        eprintln!("Lines without a timestamp, for example:");
+        for line in &stats.unmatched_samples {
+            eprintln!(
+                "  {}",
+                line.chars().take(MAX_SAMPLE_LINE_CHARS).collect::<String>()
+            );
+        }
+    }
+}
+
+/// Formats a duration in nanoseconds like "2d 3h 4m 5.006s".
+fn format_duration(nanoseconds: i64) -> String {
+    let seconds = nanoseconds / 1_000_000_000;
+    let units = [
+        (seconds / 86_400, "d"),
+        (seconds / 3_600 % 24, "h"),
+        (seconds / 60 % 60, "m"),
+    ];
+    let mut parts: Vec<String> = units
+        .iter()
+        .skip_while(|(value, _)| *value == 0)
+        .map(|(value, unit)| format!("{}{}", value, unit))
+        .collect();
+    let fraction = format!("{:09}", nanoseconds % 1_000_000_000);
+    let fraction = fraction.trim_end_matches('0');
+    match fraction {
+        "" => parts.push(format!("{}s", seconds % 60)),
+        _ => parts.push(format!("{}.{}s", seconds % 60, fraction)),
+    }
+    parts.join(" ")
+}
+
+fn parse_delimiter(s: &str) -> Result<char> {
+    match s {
+        "tab" | "\\t" => Ok('\t'),
+        _ => {
+            let mut chars = s.chars();
+            match (chars.next(), chars.next()) {
+                (Some(delimiter), None) => Ok(delimiter),
+                _ => Err(anyhow!("expected a single character or 'tab'")),
+            }
+        }
+    }
+}
+
+fn print_presets() {
+    let name_width = krapslog::PRESETS
+        .iter()
+        .map(|preset| preset.name.len())
+        .max()
+        .unwrap_or(0);
+    for preset in krapslog::PRESETS {
+        println!("{:name_width$}  {}", preset.name, preset.description);
+        println!("{:name_width$}  format:  {}", "", preset.format);
+        println!("{:name_width$}  example: {}", "", preset.sample);
+    }
 }
@@ -0,0 +1,294 @@
+use anyhow::Result;
+use std::io::Read;
+use std::ops::Range;
+
+/// Compression formats that are recognized by their magic bytes and decompressed transparently.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    None,
+    Gzip,
+    Zstd,
+    Bzip2,
+    Xz,
+}
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+const BZIP2_MAGIC: &[u8] = b"BZh";
+const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+
+impl Compression {
+    /// Number of bytes at the start of a file that `detect` needs to recognize every format.
+    pub const HEADER_LEN: usize = XZ_MAGIC.len();
+
+    /// Identifies the compression format from the first few bytes of a file.
+    pub fn detect(header: &[u8]) -> Self {
+        if header.starts_with(GZIP_MAGIC) {
+            Compression::Gzip
+        } else if header.starts_with(ZSTD_MAGIC) {
+            Compression::Zstd
+        } else if header.starts_with(BZIP2_MAGIC) {
+            Compression::Bzip2
+        } else if header.starts_with(XZ_MAGIC) {
+            Compression::Xz
+        } else {
+            Compression::None
+        }
+    }
+
+    /// Wraps `reader` in a decoder for this format. Concatenated streams, like those written by
+    /// `pigz` or `zstd -T0`, are decoded one after the other.
+    pub fn decoder<'a, R: Read + Send + 'a>(self, reader: R) -> Result<Box<dyn Read + Send + 'a>> {
+        Ok(match self {
+            Compression::None => Box::new(reader),
+            Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+            Compression::Zstd => Box::new(zstd::stream::read::Decoder::new(reader)?),
+            Compression::Bzip2 => Box::new(bzip2::read::MultiBzDecoder::new(reader)),
+            Compression::Xz => Box::new(liblzma::read::XzDecoder::new_multi_decoder(reader)),
+        })
+    }
+
+    /// Splits compressed data into runs of whole, independently decodable members that can be
+    /// decompressed in parallel: bgzip blocks for gzip, and frames for zstd. Returns at most
+    /// `count` ranges of roughly equal size, or a single range if the data can't be split.
+    pub fn split(self, data: &[u8], count: usize) -> Vec<Range<usize>> {
+        let members = match self {
+            Compression::Gzip => bgzf_blocks(data),
+            Compression::Zstd => zstd_frames(data),
+            _ => None,
+        };
+        let whole = 0..data.len();
+        let Some(members) = members else {
+            return vec![whole];
+        };
+
+        let target_len = data.len().div_ceil(count.max(1));
+        let mut ranges = vec![];
+        let mut start = 0;
+        for member in members {
+            if member.end - start >= target_len {
+                ranges.push(start..member.end);
+                start = member.end;
+            }
+        }
+        if start < data.len() || ranges.is_empty() {
+            ranges.push(start..data.len());
+        }
+        ranges
+    }
+}
+
+/// Finds the blocks of a BGZF file (bgzip), which records the size of each gzip member in an extra
+/// field. Returns `None` if any member lacks it.
+fn bgzf_blocks(data: &[u8]) -> Option<Vec<Range<usize>>> {
+    let mut blocks = vec![];
+    let mut start = 0;
+    while start < data.len() {
+        let header = data.get(start..start + 18)?;
+        let has_extra_field = header[3] & 0x04 != 0;
+        let is_bgzf = header.starts_with(GZIP_MAGIC)
+            && has_extra_field
+            && header[12..16] == [b'B', b'C', 2, 0];
+        if !is_bgzf {
+            return None;
+        }
+        let block_size = u16::from_le_bytes([header[16], header[17]]) as usize + 1;
+        let end = start + block_size;
+        if end > data.len() {
+            return None;
+        }
+        blocks.push(start..end);
+        start = end;
+    }
+    Some(blocks)
+}
+
+/// Finds the frames of a zstd file by walking the block headers, without decompressing anything.
+/// Returns `None` if the data isn't a well-formed sequence of frames.
+fn zstd_frames(data: &[u8]) -> Option<Vec<Range<usize>>> {
+    let read_u32 = |at: usize| -> Option<u32> {
+        Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?))
+    };
+
+    let mut frames = vec![];
+    let mut start = 0;
+    while start < data.len() {
+        let magic = read_u32(start)?;
+        let end = if magic & 0xffff_fff0 == 0x184d_2a50 {
+            // A skippable frame, which holds metadata
+            start + 8 + read_u32(start + 4)? as usize
+        } else if magic == 0xfd2f_b528 {
+            let descriptor = *data.get(start + 4)?;
+            let single_segment = descriptor & 0x20 != 0;
+            let has_checksum = descriptor & 0x04 != 0;
+            let dictionary_id_len = [0, 1, 2, 4][(descriptor & 0x03) as usize];
+            let content_size_len = match descriptor >> 6 {
+                0 if single_segment => 1,
+                0 => 0,
+                1 => 2,
+                2 => 4,
+                _ => 8,
+            };
+            let window_descriptor_len = usize::from(!single_segment);
+            let mut position =
+                start + 5 + window_descriptor_len + dictionary_id_len + content_size_len;
+            loop {
+                let block_header = data.get(position..position + 3)?;
+                let block_header =
+                    u32::from_le_bytes([block_header[0], block_header[1], block_header[2], 0]);
+                let is_last = block_header & 1 != 0;
+                let block_size = (block_header >> 3) as usize;
+                position += 3 + match (block_header >> 1) & 0x03 {
+                    // Run-length encoded blocks store a single byte
+                    1 => 1,
+                    3 => return None,
+                    _ => block_size,
+                };
+                if is_last {
+                    break;
+                }
+            }
+            position + if has_checksum { 4 } else { 0 }
+        } else {
+            return None;
+        };
+        if end > data.len() {
+            return None;
+        }
+        frames.push(start..end);
+        start = end;
+    }
+    Some(frames)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const LOG: &str = "[23/Nov/2019:06:26:40 +0000] GET /\n[23/Nov/2019:06:26:41 +0000] GET /\n";
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn decompress(compression: Compression, data: &[u8]) -> String {
+        let mut decompressed = String::new();
+        compression
+            .decoder(data)
+            .unwrap()
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        decompressed
+    }
+
+    /// Builds a BGZF block around a deflate stream, the way bgzip does.
+    fn bgzf_block(data: &[u8]) -> Vec<u8> {
+        let mut encoder =
+            flate2::write::DeflateEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(data).unwrap();
+        let deflated = encoder.finish().unwrap();
+
+        let mut crc = flate2::Crc::new();
+        crc.update(data);
+        let block_size = (18 + deflated.len() + 8 - 1) as u16;
+        let mut block = vec![
+            0x1f, 0x8b, 8, 4, 0, 0, 0, 0, 0, 0xff, 6, 0, b'B', b'C', 2, 0,
+        ];
+        block.extend_from_slice(&block_size.to_le_bytes());
+        block.extend_from_slice(&deflated);
+        block.extend_from_slice(&crc.sum().to_le_bytes());
+        block.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        block
+    }
+
+    #[test]
+    fn compression_detect() {
+        assert_eq!(Compression::detect(&gzip(b"")), Compression::Gzip);
+        assert_eq!(
+            Compression::detect(&zstd::encode_all(LOG.as_bytes(), 0).unwrap()),
+            Compression::Zstd
+        );
+        assert_eq!(Compression::detect(b"BZh91AY&SY"), Compression::Bzip2);
+        assert_eq!(
+            Compression::detect(b"\xfd7zXZ\x00\x00\x04"),
+            Compression::Xz
+        );
+        assert_eq!(Compression::detect(LOG.as_bytes()), Compression::None);
+        assert_eq!(Compression::detect(b""), Compression::None);
+    }
+
+    #[test]
+    fn compression_decoder() {
+        assert_eq!(decompress(Compression::None, LOG.as_bytes()), LOG);
+
+        let mut concatenated = gzip(&LOG.as_bytes()[..20]);
+        concatenated.extend(gzip(&LOG.as_bytes()[20..]));
+        assert_eq!(decompress(Compression::Gzip, &concatenated), LOG);
+
+        let zstd = zstd::encode_all(LOG.as_bytes(), 0).unwrap();
+        assert_eq!(decompress(Compression::Zstd, &zstd), LOG);
+
+        let mut encoder = bzip2::write::BzEncoder::new(Vec::new(), bzip2::Compression::fast());
+        encoder.write_all(LOG.as_bytes()).unwrap();
+        assert_eq!(
+            decompress(Compression::Bzip2, &encoder.finish().unwrap()),
+            LOG
+        );
+
+        let mut encoder = liblzma::write::XzEncoder::new(Vec::new(), 1);
+        encoder.write_all(LOG.as_bytes()).unwrap();
+        assert_eq!(decompress(Compression::Xz, &encoder.finish().unwrap()), LOG);
+    }
+
+    #[test]
+    fn compression_split_bgzf() {
+        let lines: Vec<&str> = LOG.split_inclusive('\n').collect();
+        let mut data = vec![];
+        for _ in 0..3 {
+            data.extend(bgzf_block(lines[0].as_bytes()));
+            data.extend(bgzf_block(lines[1].as_bytes()));
+        }
+        // bgzip ends files with an empty block
+        data.extend(bgzf_block(b""));
+
+        let ranges = Compression::Gzip.split(&data, 3);
+        assert_eq!(ranges.len(), 3);
+        assert_eq!(ranges.first().unwrap().start, 0);
+        assert_eq!(ranges.last().unwrap().end, data.len());
+        assert!(ranges.windows(2).all(|pair| pair[0].end == pair[1].start));
+        let decompressed: String = ranges
+            .iter()
+            .map(|range| decompress(Compression::Gzip, &data[range.clone()]))
+            .collect();
+        assert_eq!(decompressed, LOG.repeat(3));
+
+        // Ordinary gzip files can't be split
+        let data = gzip(LOG.as_bytes());
+        assert_eq!(Compression::Gzip.split(&data, 3).len(), 1);
+    }
+
+    #[test]
+    fn compression_split_zstd() {
+        let mut data = vec![];
+        for _ in 0..4 {
+            data.extend(zstd::encode_all(LOG.as_bytes(), 0).unwrap());
+        }
+
+        let ranges = Compression::Zstd.split(&data, 2);
+        assert_eq!(ranges.len(), 2);
+        assert_eq!(ranges[0].end, ranges[1].start);
+        for range in &ranges {
+            assert_eq!(
+                decompress(Compression::Zstd, &data[range.clone()]),
+                LOG.repeat(2)
+            );
+        }
+
+        assert_eq!(Compression::Zstd.split(&data, 1).len(), 1);
+        // Truncated data can't be split
+        assert_eq!(Compression::Zstd.split(&data[..data.len() - 1], 2).len(), 1);
+    }
+}
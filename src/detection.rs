@@ -0,0 +1,148 @@
+use chrono::{TimeDelta, Utc};
+
+use crate::presets::PRESETS;
+use crate::timestamp_finder::TimestampFinder;
+
+/// The earliest timestamp that detection considers plausible (1980-01-01), in nanoseconds. Loose
+/// formats like `%s` happily match unrelated numbers, which usually decode to dates near 1970.
+const EARLIEST_PLAUSIBLE_TIMESTAMP: i64 = 315_532_800_000_000_000;
+
+#[derive(Debug, PartialEq)]
+pub struct DetectedFormat {
+    /// Name of the preset that matched, e.g. "clf".
+    pub name: &'static str,
+    /// The strftime-style format that can be passed to `TimestampFinder::new`.
+    pub format: &'static str,
+    /// Number of sampled lines that contain a timestamp in this format.
+    pub matching_lines: usize,
+    /// Number of lines in the sample.
+    pub sampled_lines: usize,
+}
+
+/// Picks the preset that matches the most lines in `sample`, which is usually the first few hundred
+/// lines of a log. Returns `None` if no preset matches any line.
+pub fn detect_timestamp_format(sample: &str) -> Option<DetectedFormat> {
+    let latest_plausible_timestamp = (Utc::now() + TimeDelta::days(366))
+        .timestamp_nanos_opt()
+        .unwrap_or(i64::MAX);
+    let plausible = EARLIEST_PLAUSIBLE_TIMESTAMP..=latest_plausible_timestamp;
+    let lines: Vec<&str> = sample.lines().collect();
+
+    let mut best: Option<DetectedFormat> = None;
+    for preset in PRESETS {
+        let date_finder = TimestampFinder::new(preset.format).expect("presets are valid");
+        let matching_lines = lines
+            .iter()
+            .filter_map(|line| date_finder.find_timestamp(line))
+            .filter(|timestamp| plausible.contains(timestamp))
+            .count();
+        let is_better = match &best {
+            Some(best) => matching_lines > best.matching_lines,
+            None => matching_lines > 0,
+        };
+        if is_better {
+            best = Some(DetectedFormat {
+                name: preset.name,
+                format: preset.format,
+                matching_lines,
+                sampled_lines: lines.len(),
+            });
+        }
+    }
+
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detected_format(sample: &str) -> Option<&'static str> {
+        detect_timestamp_format(sample).map(|detected| detected.name)
+    }
+
+    #[test]
+    fn detect_timestamp_format_common_formats() {
+        assert_eq!(
+            detected_format(
+                "10.1.1.10 - - [23/Nov/2019:06:26:40 +0000] \"GET / HTTP/1.1\" 200 913"
+            ),
+            Some("clf")
+        );
+        assert_eq!(
+            detected_format("Nov 23 06:26:40 ip-10-1-1-1 haproxy[20128]: 10.1.1.10:57305 [23/Nov/2019:06:26:40.781] public myapp/i-05fa49c0e7db8c328 0/0/0/78/78 206 913/458"),
+            Some("haproxy")
+        );
+        assert_eq!(
+            detected_format(
+                "{\"level\":\"info\",\"ts\":\"2024-01-02T03:04:05.123Z\",\"msg\":\"ok\"}"
+            ),
+            Some("rfc3339")
+        );
+        assert_eq!(
+            detected_format(
+                "<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 - msg"
+            ),
+            Some("rfc5424")
+        );
+        assert_eq!(
+            detected_format("2024-01-02 03:04:05,123 INFO  [main] com.example.App - started"),
+            Some("log4j")
+        );
+        assert_eq!(
+            detected_format("2024/01/02 03:04:05 [error] 1234#0: *5 open() failed"),
+            Some("nginx-error")
+        );
+        assert_eq!(
+            detected_format("[Tue Jan 02 03:04:05.123456 2024] [core:error] [pid 1234] AH00126"),
+            Some("ctime")
+        );
+        assert_eq!(
+            detected_format("E0102 03:04:05.123456       1 reflector.go:138] failed to list"),
+            Some("k8s")
+        );
+        assert_eq!(
+            detected_format("Nov 23 06:26:40 ip-10-1-1-1 sshd[20128]: Accepted publickey"),
+            Some("syslog")
+        );
+        assert_eq!(
+            detected_format("1704164645 10.1.1.10 GET / 200"),
+            Some("epoch")
+        );
+        assert_eq!(
+            detected_format("1704164645123 10.1.1.10 GET / 200"),
+            Some("epoch_ms")
+        );
+        assert_eq!(
+            detected_format("1704164645123456789 10.1.1.10 GET / 200"),
+            Some("epoch_ns")
+        );
+        assert_eq!(
+            detected_format("1704164645.5 10.1.1.10 GET / 200"),
+            Some("epoch_frac")
+        );
+    }
+
+    #[test]
+    fn detect_timestamp_format_majority_wins() {
+        let sample = "2024-01-02T03:04:05Z started
+2024-01-02 03:04:06,000 INFO one
+2024-01-02 03:04:07,000 INFO two
+2024-01-02 03:04:08,000 INFO three
+garbage
+";
+        let detected = detect_timestamp_format(sample).unwrap();
+        assert_eq!(detected.name, "log4j");
+        assert_eq!(detected.matching_lines, 3);
+        assert_eq!(detected.sampled_lines, 5);
+    }
+
+    #[test]
+    fn detect_timestamp_format_no_match() {
+        assert_eq!(
+            detect_timestamp_format("no timestamps\nin 10.1.1.10 here\n"),
+            None
+        );
+        assert_eq!(detect_timestamp_format(""), None);
+    }
+}
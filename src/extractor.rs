@@ -0,0 +1,506 @@
+use anyhow::{anyhow, Result};
+use regex::Regex;
+use serde_json::Value;
+use std::borrow::Cow;
+use std::str::FromStr;
+
+/// Name of the capture group that holds the timestamp in a custom regex.
+const TIMESTAMP_GROUP: &str = "ts";
+
+/// Selects the part of each log line that holds the timestamp.
+#[derive(Clone, Debug, Default)]
+pub enum Extractor {
+    /// Search the whole line.
+    #[default]
+    Line,
+    /// Read a field from lines that are JSON objects. Holds a JSON pointer (RFC 6901) like "/ts".
+    Json(String),
+    /// Read the value of a key from logfmt lines like `level=info ts=2024-01-02T03:04:05Z`. Holds
+    /// the keys to look for, in order of preference.
+    Logfmt(Vec<String>),
+    /// Read a column from CSV or TSV lines. Values may be quoted, with quotes inside them doubled.
+    Csv { column: CsvColumn, delimiter: char },
+    /// Read the text captured by the group named "ts" in a regex.
+    Regex(Regex),
+}
+
+impl PartialEq for Extractor {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Extractor::Line, Extractor::Line) => true,
+            (Extractor::Json(a), Extractor::Json(b)) => a == b,
+            (Extractor::Logfmt(a), Extractor::Logfmt(b)) => a == b,
+            (
+                Extractor::Csv {
+                    column: a,
+                    delimiter: a_delimiter,
+                },
+                Extractor::Csv {
+                    column: b,
+                    delimiter: b_delimiter,
+                },
+            ) => a == b && a_delimiter == b_delimiter,
+            (Extractor::Regex(a), Extractor::Regex(b)) => a.as_str() == b.as_str(),
+            _ => false,
+        }
+    }
+}
+
+/// A column of a CSV file.
+#[derive(Clone, Debug, PartialEq)]
+pub enum CsvColumn {
+    /// The zero-based position of the column.
+    Index(usize),
+    /// The name of the column in the header. See `Extractor::with_header`.
+    Name(String),
+}
+
+/// Parses a column given on the command line: a one-based position like "3", or a name.
+impl FromStr for CsvColumn {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.parse::<usize>() {
+            Ok(0) => Err(anyhow!("CSV columns are numbered from 1")),
+            Ok(position) => Ok(CsvColumn::Index(position - 1)),
+            Err(_) => Ok(CsvColumn::Name(s.to_string())),
+        }
+    }
+}
+
+/// Keys that logfmt loggers commonly use for the timestamp, in order of preference.
+pub const LOGFMT_TIME_KEYS: &[&str] = &["ts", "time", "t"];
+
+/// The part of a line that an `Extractor` selected.
+#[derive(Debug, PartialEq)]
+pub enum Field<'a> {
+    Text(Cow<'a, str>),
+    /// A JSON number, or an unquoted numeric value in logfmt or CSV, which is read as a UNIX
+    /// timestamp. It's kept as text so that timestamps in nanoseconds don't lose precision.
+    Number(String),
+    /// The line doesn't have the field.
+    Missing,
+    /// The line couldn't be parsed, e.g. because it isn't valid JSON or has an unterminated quote.
+    Malformed,
+}
+
+impl Extractor {
+    /// Reads the timestamp from a JSON field, given either a top-level field name like "ts" or a
+    /// JSON pointer like "/meta/time".
+    pub fn json_field(field: &str) -> Self {
+        if field.starts_with('/') {
+            return Extractor::Json(field.to_string());
+        }
+        Extractor::Json(format!("/{}", field.replace('~', "~0").replace('/', "~1")))
+    }
+
+    /// Reads the timestamp from the value of a logfmt key. Without a key, the first of
+    /// `LOGFMT_TIME_KEYS` that appears on the line is used.
+    pub fn logfmt_key(key: Option<&str>) -> Self {
+        match key {
+            Some(key) => Extractor::Logfmt(vec![key.to_string()]),
+            None => Extractor::Logfmt(LOGFMT_TIME_KEYS.iter().map(|k| k.to_string()).collect()),
+        }
+    }
+
+    /// Reads the timestamp from a column of CSV lines, e.g. with a delimiter of ',' or '\t'.
+    pub fn csv_column(column: CsvColumn, delimiter: char) -> Self {
+        Extractor::Csv { column, delimiter }
+    }
+
+    /// Whether the extractor has to see the first line of the log to find its field, i.e. whether
+    /// it refers to a CSV column by name.
+    pub fn needs_header(&self) -> bool {
+        matches!(
+            self,
+            Extractor::Csv {
+                column: CsvColumn::Name(_),
+                ..
+            }
+        )
+    }
+
+    /// Looks up a CSV column that's referred to by name in `header`, the first line of the log.
+    /// Other extractors are returned unchanged.
+    pub fn with_header(self, header: &str) -> Result<Self> {
+        let Extractor::Csv {
+            column: CsvColumn::Name(name),
+            delimiter,
+        } = self
+        else {
+            return Ok(self);
+        };
+
+        let header = header.strip_prefix('\u{feff}').unwrap_or(header);
+        let index = csv_values(header, delimiter, usize::MAX)
+            .and_then(|values| values.iter().position(|(value, _)| value.trim() == name))
+            .ok_or_else(|| anyhow!("column '{}' isn't in the CSV header", name))?;
+        Ok(Extractor::csv_column(CsvColumn::Index(index), delimiter))
+    }
+
+    /// Reads the timestamp from the part of each line that the capture group named "ts" matches,
+    /// like `\[(?P<ts>[^\]]+)\]`.
+    pub fn timestamp_regex(pattern: &str) -> Result<Self> {
+        let regex = Regex::new(pattern)?;
+        if !regex
+            .capture_names()
+            .any(|name| name == Some(TIMESTAMP_GROUP))
+        {
+            return Err(anyhow!(
+                "timestamp regex must have a capture group named '{}', like (?P<{}>...)",
+                TIMESTAMP_GROUP,
+                TIMESTAMP_GROUP
+            ));
+        }
+        Ok(Extractor::Regex(regex))
+    }
+
+    pub fn extract<'a>(&self, line: &'a str) -> Field<'a> {
+        match self {
+            Extractor::Line => Field::Text(Cow::Borrowed(line)),
+            Extractor::Json(pointer) => {
+                if line.trim().is_empty() {
+                    return Field::Missing;
+                }
+                let mut value: Value = match serde_json::from_str(line) {
+                    Ok(value) => value,
+                    Err(_) => return Field::Malformed,
+                };
+                match value.pointer_mut(pointer).map(Value::take) {
+                    Some(Value::String(text)) => Field::Text(Cow::Owned(text)),
+                    Some(Value::Number(number)) => Field::Number(number.to_string()),
+                    _ => Field::Missing,
+                }
+            }
+            Extractor::Logfmt(keys) => logfmt_field(line, keys),
+            Extractor::Csv {
+                column: CsvColumn::Index(index),
+                delimiter,
+            } => match csv_values(line, *delimiter, index + 1) {
+                None => Field::Malformed,
+                Some(mut values) if values.len() > *index => match values.swap_remove(*index) {
+                    (value, false) if is_number(&value) => Field::Number(value.into_owned()),
+                    (value, _) => Field::Text(value),
+                },
+                Some(_) => Field::Missing,
+            },
+            // The name hasn't been looked up in the header
+            Extractor::Csv { .. } => Field::Missing,
+            Extractor::Regex(regex) => regex
+                .captures(line)
+                .and_then(|captures| captures.name(TIMESTAMP_GROUP))
+                .map_or(Field::Missing, |ts| Field::Text(Cow::Borrowed(ts.as_str()))),
+        }
+    }
+}
+
+/// Finds the value of the most preferred of `keys` on a logfmt line.
+fn logfmt_field<'a>(line: &'a str, keys: &[String]) -> Field<'a> {
+    let mut best: Option<(usize, Field<'a>)> = None;
+    let mut rest = line;
+    loop {
+        rest = rest.trim_start();
+        if rest.is_empty() {
+            break;
+        }
+
+        let key_end = rest
+            .find(|c: char| c == '=' || c.is_whitespace())
+            .unwrap_or(rest.len());
+        let key = &rest[..key_end];
+        rest = &rest[key_end..];
+
+        // A key without '=' is a flag with no value
+        let Some(after_equals) = rest.strip_prefix('=') else {
+            continue;
+        };
+        let value = match after_equals.strip_prefix('"') {
+            Some(quoted) => match unquote(quoted) {
+                Some((value, remainder)) => {
+                    rest = remainder;
+                    Field::Text(value)
+                }
+                // A truncated line may still have had the timestamp before the broken value
+                None => return best.map_or(Field::Malformed, |(_, value)| value),
+            },
+            None => {
+                let end = after_equals
+                    .find(char::is_whitespace)
+                    .unwrap_or(after_equals.len());
+                let value = &after_equals[..end];
+                rest = &after_equals[end..];
+                if is_number(value) {
+                    Field::Number(value.to_string())
+                } else {
+                    Field::Text(Cow::Borrowed(value))
+                }
+            }
+        };
+
+        if let Some(preference) = keys.iter().position(|k| k == key) {
+            if best.as_ref().is_none_or(|(best, _)| preference < *best) {
+                best = Some((preference, value));
+            }
+        }
+    }
+
+    best.map_or(Field::Missing, |(_, value)| value)
+}
+
+/// Reads a quoted logfmt value up to its closing quote, undoing backslash escapes. Returns the value
+/// and the rest of the line, or `None` if the quote is never closed.
+fn unquote(s: &str) -> Option<(Cow<'_, str>, &str)> {
+    let mut escaped = false;
+    let mut has_escapes = false;
+    for (i, c) in s.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => {
+                escaped = true;
+                has_escapes = true;
+            }
+            '"' => {
+                let value = &s[..i];
+                let value = if has_escapes {
+                    Cow::Owned(unescape(value))
+                } else {
+                    Cow::Borrowed(value)
+                };
+                return Some((value, &s[i + 1..]));
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+fn unescape(s: &str) -> String {
+    let mut unescaped = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => unescaped.push('\n'),
+            Some('t') => unescaped.push('\t'),
+            Some(escaped) => unescaped.push(escaped),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+/// Splits a CSV line into at most `limit` values, each with whether it was quoted. Returns `None`
+/// if a quote is never closed.
+fn csv_values(line: &str, delimiter: char, limit: usize) -> Option<Vec<(Cow<'_, str>, bool)>> {
+    let mut values = vec![];
+    let mut rest = line.strip_suffix('\r').unwrap_or(line);
+    while values.len() < limit {
+        let value_end = match rest.strip_prefix('"') {
+            Some(quoted) => {
+                let (value, remainder) = csv_unquote(quoted)?;
+                values.push((value, true));
+                // Anything between the closing quote and the delimiter is ignored
+                rest = remainder;
+                rest.find(delimiter)
+            }
+            None => {
+                let end = rest.find(delimiter);
+                values.push((Cow::Borrowed(&rest[..end.unwrap_or(rest.len())]), false));
+                end
+            }
+        };
+        match value_end {
+            Some(end) => rest = &rest[end + delimiter.len_utf8()..],
+            None => break,
+        }
+    }
+    Some(values)
+}
+
+/// Reads a quoted CSV value up to its closing quote. Returns the value and the rest of the line.
+fn csv_unquote(s: &str) -> Option<(Cow<'_, str>, &str)> {
+    let mut search_from = 0;
+    while let Some(quote) = s[search_from..].find('"').map(|i| search_from + i) {
+        if s[quote + 1..].starts_with('"') {
+            search_from = quote + 2;
+            continue;
+        }
+        let value = &s[..quote];
+        let value = if value.contains("\"\"") {
+            Cow::Owned(value.replace("\"\"", "\""))
+        } else {
+            Cow::Borrowed(value)
+        };
+        return Some((value, &s[quote + 1..]));
+    }
+    None
+}
+
+fn is_number(s: &str) -> bool {
+    let digits = s.strip_prefix('-').unwrap_or(s);
+    !digits.is_empty()
+        && digits.chars().all(|c| c.is_ascii_digit() || c == '.')
+        && digits.matches('.').count() <= 1
+        && digits.chars().any(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> Field<'_> {
+        Field::Text(Cow::Borrowed(s))
+    }
+
+    #[test]
+    fn extractor_json_field() {
+        assert_eq!(Extractor::json_field("ts"), Extractor::Json("/ts".into()));
+        assert_eq!(
+            Extractor::json_field("a/b~c"),
+            Extractor::Json("/a~1b~0c".into())
+        );
+        assert_eq!(
+            Extractor::json_field("/meta/time"),
+            Extractor::Json("/meta/time".into())
+        );
+    }
+
+    #[test]
+    fn extractor_extract_json() {
+        let line = r#"{"ts":"2024-01-02T03:04:05Z","user":{"created_at":"2019-11-23T06:26:40Z"}}"#;
+        assert_eq!(
+            Extractor::json_field("ts").extract(line),
+            text("2024-01-02T03:04:05Z")
+        );
+        assert_eq!(
+            Extractor::json_field("/user/created_at").extract(line),
+            text("2019-11-23T06:26:40Z")
+        );
+        assert_eq!(Extractor::json_field("time").extract(line), Field::Missing);
+
+        assert_eq!(
+            Extractor::json_field("ts").extract(r#"{"ts":1704164645123456789}"#),
+            Field::Number("1704164645123456789".into())
+        );
+        assert_eq!(
+            Extractor::json_field("ts").extract(r#"{"ts":1704164645.25}"#),
+            Field::Number("1704164645.25".into())
+        );
+
+        assert_eq!(
+            Extractor::json_field("ts").extract("panic: runtime error"),
+            Field::Malformed
+        );
+        assert_eq!(Extractor::json_field("ts").extract("  "), Field::Missing);
+    }
+
+    #[test]
+    fn extractor_extract_logfmt() {
+        let extractor = Extractor::logfmt_key(None);
+        assert_eq!(
+            extractor.extract("level=info ts=2024-01-02T03:04:05Z msg=started"),
+            text("2024-01-02T03:04:05Z")
+        );
+        assert_eq!(
+            extractor.extract(r#"t=1704164645 level=info msg="started""#),
+            Field::Number("1704164645".into())
+        );
+        // ts is preferred over time wherever they appear
+        assert_eq!(
+            extractor.extract(r#"time="2019-11-23 06:26:40" ts=2024-01-02T03:04:05Z"#),
+            text("2024-01-02T03:04:05Z")
+        );
+        assert_eq!(extractor.extract("level=info msg=started"), Field::Missing);
+
+        let extractor = Extractor::logfmt_key(Some("when"));
+        assert_eq!(
+            extractor.extract(
+                r#"msg="say \"when=2019-11-23\" please" debug when="2024-01-02 03:04:05""#
+            ),
+            text("2024-01-02 03:04:05")
+        );
+        assert_eq!(
+            extractor.extract(r#"when="2024-01-02 \"03:04:05\"""#),
+            text(r#"2024-01-02 "03:04:05""#)
+        );
+        assert_eq!(
+            extractor.extract(r#"when="2024-01-02 03:04:05" msg="truncat"#),
+            text("2024-01-02 03:04:05")
+        );
+        assert_eq!(
+            extractor.extract(r#"msg="truncat when=2024-01-02"#),
+            Field::Malformed
+        );
+    }
+
+    #[test]
+    fn csv_column_from_str() {
+        assert_eq!("3".parse::<CsvColumn>().unwrap(), CsvColumn::Index(2));
+        assert_eq!(
+            "timestamp".parse::<CsvColumn>().unwrap(),
+            CsvColumn::Name("timestamp".into())
+        );
+        assert!("0".parse::<CsvColumn>().is_err());
+    }
+
+    #[test]
+    fn extractor_extract_csv() {
+        let extractor = Extractor::csv_column(CsvColumn::Index(1), ',');
+        assert_eq!(
+            extractor.extract("2019-11-23,2024-01-02 03:04:05,GET"),
+            text("2024-01-02 03:04:05")
+        );
+        assert_eq!(
+            extractor.extract(r#""a, ""quoted"" value","2024-01-02 03:04:05",GET"#),
+            text("2024-01-02 03:04:05")
+        );
+        assert_eq!(
+            extractor.extract(r#"a,"say ""hi"", please""#),
+            text(r#"say "hi", please"#)
+        );
+        assert_eq!(
+            extractor.extract("a,1704164645\r"),
+            Field::Number("1704164645".into())
+        );
+        assert_eq!(extractor.extract("a"), Field::Missing);
+        assert_eq!(extractor.extract(r#""unterminated,b"#), Field::Malformed);
+
+        let extractor = Extractor::csv_column(CsvColumn::Index(2), '\t');
+        assert_eq!(
+            extractor.extract("2019-11-23\tIAD89-C1\t2024-01-02T03:04:05Z"),
+            text("2024-01-02T03:04:05Z")
+        );
+    }
+
+    #[test]
+    fn extractor_with_header() {
+        let extractor = Extractor::csv_column(CsvColumn::Name("timestamp".into()), ',');
+        assert!(extractor.needs_header());
+        assert_eq!(extractor.extract("a,b"), Field::Missing);
+
+        let extractor = extractor
+            .with_header("\u{feff}created_at,\"timestamp\",method")
+            .unwrap();
+        assert!(!extractor.needs_header());
+        assert_eq!(extractor, Extractor::csv_column(CsvColumn::Index(1), ','));
+
+        let extractor = Extractor::csv_column(CsvColumn::Name("timestamp".into()), ',');
+        assert!(extractor.with_header("created_at,method").is_err());
+    }
+
+    #[test]
+    fn extractor_extract_timestamp_regex() {
+        let extractor = Extractor::timestamp_regex(r"\] \[(?P<ts>[^\]]+)\]").unwrap();
+        assert_eq!(
+            extractor.extract("[app] [2024-01-02 03:04:05] [2019-11-23 06:26:40] started"),
+            text("2024-01-02 03:04:05")
+        );
+        assert_eq!(extractor.extract("[app] started"), Field::Missing);
+
+        assert!(Extractor::timestamp_regex(r"\[(?P<time>[^\]]+)\]").is_err());
+        assert!(Extractor::timestamp_regex(r"(?P<ts>[").is_err());
+    }
+}
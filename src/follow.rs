@@ -0,0 +1,159 @@
+use anyhow::Result;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::PathBuf;
+
+/// Reads the lines that are appended to a log file, like `tail -F`. It copes with the file being
+/// truncated, and with it being replaced by a new one when logs are rotated. The file doesn't
+/// need to exist yet.
+pub struct Follower {
+    path: PathBuf,
+    file: Option<File>,
+    position: u64,
+    /// The start of a line whose end hasn't been written yet
+    partial: Vec<u8>,
+}
+
+impl Follower {
+    /// Follows the file at `path` from its start.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Follower {
+            path: path.into(),
+            file: None,
+            position: 0,
+            partial: vec![],
+        }
+    }
+
+    /// Returns the complete lines that were written since the last call, reading up to about
+    /// `limit` bytes. Returns nothing if there's nothing new.
+    pub fn read_lines(&mut self, limit: usize) -> Result<Vec<u8>> {
+        let mut lines = vec![];
+        loop {
+            let Some(file) = &mut self.file else {
+                match File::open(&self.path) {
+                    Ok(file) => self.file = Some(file),
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(lines),
+                    Err(e) => return Err(e.into()),
+                }
+                continue;
+            };
+
+            if file.metadata()?.len() < self.position {
+                // Truncated, so start over from the top
+                file.seek(SeekFrom::Start(0))?;
+                self.position = 0;
+                self.partial.clear();
+            }
+            let read = file
+                .take(limit.saturating_sub(lines.len()) as u64)
+                .read_to_end(&mut self.partial)?;
+            self.position += read as u64;
+            if let Some(end) = self.partial.iter().rposition(|&b| b == b'\n') {
+                lines.extend(self.partial.drain(..=end));
+            }
+            if read > 0 || !self.replaced()? {
+                return Ok(lines);
+            }
+
+            // Everything's been read from the old file, so any unfinished line is as long as it
+            // will get before moving on to the new one
+            if !self.partial.is_empty() {
+                lines.append(&mut self.partial);
+                lines.push(b'\n');
+            }
+            self.file = None;
+            self.position = 0;
+        }
+    }
+
+    /// Checks whether the path now leads to a different file than the one that's open.
+    fn replaced(&self) -> Result<bool> {
+        let Some(file) = &self.file else {
+            return Ok(false);
+        };
+        match fs::metadata(&self.path) {
+            Ok(metadata) => Ok(!same_file(&file.metadata()?, &metadata)),
+            // Rotated away, but the new file hasn't been created yet
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn same_file(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    a.dev() == b.dev() && a.ino() == b.ino()
+}
+
+#[cfg(not(unix))]
+fn same_file(a: &fs::Metadata, b: &fs::Metadata) -> bool {
+    a.created().ok() == b.created().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const LIMIT: usize = 1024 * 1024;
+
+    fn append(path: &std::path::Path, data: &str) {
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .unwrap();
+        file.write_all(data.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn follower_reads_appended_lines() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        let mut follower = Follower::new(&path);
+        assert_eq!(follower.read_lines(LIMIT).unwrap(), b"");
+
+        append(&path, "one\ntw");
+        assert_eq!(follower.read_lines(LIMIT).unwrap(), b"one\n");
+        assert_eq!(follower.read_lines(LIMIT).unwrap(), b"");
+        append(&path, "o\nthree\n");
+        assert_eq!(follower.read_lines(LIMIT).unwrap(), b"two\nthree\n");
+
+        append(&path, "four\nfive\n");
+        assert_eq!(follower.read_lines(6).unwrap(), b"four\n");
+        assert_eq!(follower.read_lines(6).unwrap(), b"five\n");
+    }
+
+    #[test]
+    fn follower_handles_truncation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        append(&path, "one\ntwo\n");
+        let mut follower = Follower::new(&path);
+        assert_eq!(follower.read_lines(LIMIT).unwrap(), b"one\ntwo\n");
+
+        File::create(&path).unwrap();
+        append(&path, "new\n");
+        assert_eq!(follower.read_lines(LIMIT).unwrap(), b"new\n");
+    }
+
+    #[test]
+    fn follower_handles_rotation() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("app.log");
+        append(&path, "one\n");
+        let mut follower = Follower::new(&path);
+        assert_eq!(follower.read_lines(LIMIT).unwrap(), b"one\n");
+
+        let rotated = dir.path().join("app.log.1");
+        fs::rename(&path, &rotated).unwrap();
+        append(&rotated, "two\nunfinished");
+        assert_eq!(follower.read_lines(LIMIT).unwrap(), b"two\n");
+
+        append(&path, "three\n");
+        assert_eq!(follower.read_lines(LIMIT).unwrap(), b"unfinished\nthree\n");
+        assert_eq!(follower.read_lines(LIMIT).unwrap(), b"");
+    }
+}
@@ -1,43 +1,216 @@
+mod aggregate;
+mod decompress;
+mod detection;
+mod extractor;
+mod follow;
+mod line_filter;
+mod presets;
+mod quantile_sketch;
+mod stats;
 mod time_marker;
+mod time_range;
+mod time_span;
 mod timestamp_finder;
+mod timezone;
 
 use anyhow::Result;
+use std::borrow::Cow;
+use std::collections::HashMap;
 use std::io::{prelude::*, BufReader};
 
-use crate::timestamp_finder::TimestampFinder;
+pub use crate::aggregate::Aggregate;
+pub use crate::decompress::Compression;
+pub use crate::detection::{detect_timestamp_format, DetectedFormat};
+pub use crate::extractor::{CsvColumn, Extractor, Field};
+pub use crate::follow::Follower;
+pub use crate::line_filter::LineFilter;
+pub use crate::presets::{find_preset, Preset, PRESETS};
+pub use crate::quantile_sketch::{QuantileSketch, SketchTimeline};
+pub use crate::stats::{ScanStats, UNMATCHED_SAMPLE_LINES};
+pub use crate::time_range::{TimeBound, TimeRange};
+pub use crate::time_span::TimeSpan;
+pub use crate::timestamp_finder::{Occurrence, ScannedLine, TimestampFinder};
+pub use crate::timezone::Timezone;
 
 const SPARKS: &[&str] = &["▁", "▂", "▃", "▄", "▅", "▆", "▇", "█"];
 
 pub fn build_sparkline(timestamps: &[i64], width: usize, height: usize) -> String {
-    let timestamp_frequencies = timestamp_frequency_distribution(timestamps, width);
+    let timestamp_frequencies =
+        timestamp_frequency_distribution(timestamps, time_range(timestamps), width);
+    render_sparklines(&[counts(timestamp_frequencies)], height).remove(0)
+}
+
+/// Builds one sparkline per log for logs that are compared with each other. They share a time
+/// axis and a vertical scale, so the same column covers the same span of time in every log, and
+/// equal heights mean equal numbers of lines. At least one log must have a timestamp.
+pub fn build_sparklines(logs: &[&[i64]], width: usize, height: usize) -> Vec<String> {
+    let all_timestamps = logs.iter().flat_map(|timestamps| timestamps.iter());
+    let range = (
+        *all_timestamps.clone().min().unwrap(),
+        *all_timestamps.max().unwrap(),
+    );
+    build_sparklines_in_range(logs, range, width, height)
+}
+
+/// Like [`build_sparklines`], but the time axis runs from the first to the last timestamp of
+/// `range`, whatever the logs hold. Timestamps outside of it are left out.
+pub fn build_sparklines_in_range(
+    logs: &[&[i64]],
+    range: (i64, i64),
+    width: usize,
+    height: usize,
+) -> Vec<String> {
+    let frequencies: Vec<_> = logs
+        .iter()
+        .map(|timestamps| counts(timestamp_frequency_distribution(timestamps, range, width)))
+        .collect();
+    render_sparklines(&frequencies, height)
+}
+
+/// Like [`build_sparklines`], but with a column for each bucket of `bucket_size` nanoseconds,
+/// like a minute or an hour. There are `bucket_count` of them, starting with the one that begins
+/// at `first_bucket`. Timestamps outside of the buckets are left out.
+pub fn build_sparklines_in_buckets(
+    logs: &[&[i64]],
+    first_bucket: i64,
+    bucket_count: usize,
+    bucket_size: i64,
+    height: usize,
+) -> Vec<String> {
+    let frequencies: Vec<_> = logs
+        .iter()
+        .map(|timestamps| {
+            counts(frequency_distribution(
+                timestamps,
+                bucket_count,
+                |timestamp| column_in_buckets(timestamp, first_bucket, bucket_count, bucket_size),
+            ))
+        })
+        .collect();
+    render_sparklines(&frequencies, height)
+}
+
+/// Like [`build_sparklines_in_range`], but each column shows the `aggregate` of the values whose
+/// timestamps fall into it, rather than how many there are. Each value comes with the timestamp of
+/// its line. Columns without any values are left blank.
+pub fn build_value_sparklines_in_range(
+    logs: &[&[(i64, f64)]],
+    aggregate: Aggregate,
+    range: (i64, i64),
+    width: usize,
+    height: usize,
+) -> Vec<String> {
+    let columns: Vec<_> = logs
+        .iter()
+        .map(|values| {
+            value_distribution(values, aggregate, width, |timestamp| {
+                column_in_range(timestamp, range, width)
+            })
+        })
+        .collect();
+    render_sparklines(&columns, height)
+}
+
+/// Like [`build_sparklines_in_buckets`], but each column shows the `aggregate` of the values in
+/// its bucket, as in [`build_value_sparklines_in_range`].
+pub fn build_value_sparklines_in_buckets(
+    logs: &[&[(i64, f64)]],
+    aggregate: Aggregate,
+    first_bucket: i64,
+    bucket_count: usize,
+    bucket_size: i64,
+    height: usize,
+) -> Vec<String> {
+    let columns: Vec<_> = logs
+        .iter()
+        .map(|values| {
+            value_distribution(values, aggregate, bucket_count, |timestamp| {
+                column_in_buckets(timestamp, first_bucket, bucket_count, bucket_size)
+            })
+        })
+        .collect();
+    render_sparklines(&columns, height)
+}
+
+/// Builds a sparkline for each of `percentiles` of the values, like 50, 90 and 99, in the order
+/// given. They share a time axis like [`build_value_sparklines_in_range`], and a vertical scale so
+/// that the tail can be told apart from the middle. The percentiles of each column are estimated
+/// from the slots of the [`SketchTimeline`] that fall into it.
+pub fn build_percentile_sparklines_in_range(
+    values: &SketchTimeline,
+    percentiles: &[f64],
+    range: (i64, i64),
+    width: usize,
+    height: usize,
+) -> Vec<String> {
+    let sketches = values.buckets(width, |timestamp| column_in_range(timestamp, range, width));
+    render_percentiles(&sketches, percentiles, height)
+}
+
+/// Like [`build_percentile_sparklines_in_range`], but with a column for each bucket, as in
+/// [`build_sparklines_in_buckets`].
+pub fn build_percentile_sparklines_in_buckets(
+    values: &SketchTimeline,
+    percentiles: &[f64],
+    first_bucket: i64,
+    bucket_count: usize,
+    bucket_size: i64,
+    height: usize,
+) -> Vec<String> {
+    let sketches = values.buckets(bucket_count, |timestamp| {
+        column_in_buckets(timestamp, first_bucket, bucket_count, bucket_size)
+    });
+    render_percentiles(&sketches, percentiles, height)
+}
+
+/// Finds the start of the bucket of `bucket_size` nanoseconds that `timestamp` falls into.
+/// Buckets start on multiples of their size since the UNIX epoch on the wall clock in `timezone`,
+/// so that buckets of a minute, an hour or a day start on the minute, the hour or at midnight
+/// there.
+pub fn bucket_start(timestamp: i64, bucket_size: i64, timezone: Timezone) -> i64 {
+    let offset = timezone.utc_offset(timestamp);
+    (timestamp + offset).div_euclid(bucket_size) * bucket_size - offset
+}
+
+/// Renders sparklines on a shared vertical scale. Each column holds the value that sets its
+/// height, or `None` to leave it blank.
+fn render_sparklines(columns: &[Vec<Option<f64>>], height: usize) -> Vec<String> {
+    let values = || columns.iter().flatten().flatten().copied();
     let (min, max) = (
-        *timestamp_frequencies.iter().min().unwrap() as f64,
-        *timestamp_frequencies.iter().max().unwrap() as f64,
+        values().reduce(f64::min).unwrap_or(0.0),
+        values().reduce(f64::max).unwrap_or(0.0),
     );
-    let mut canvas = vec![vec![" "; width]; height];
+    columns
+        .iter()
+        .map(|columns| render_sparkline(columns, min, max, height))
+        .collect()
+}
+
+fn render_sparkline(columns: &[Option<f64>], min: f64, max: f64, height: usize) -> String {
+    let mut canvas = vec![vec![" "; columns.len()]; height];
     let slots_per_line = SPARKS.len();
 
-    timestamp_frequencies
-        .iter()
-        .enumerate()
-        .for_each(|(column, freq)| {
-            let proportion = (*freq as f64 - min) / (max - min);
-            let scaled_proportion = proportion * height as f64;
-            let mut slots_left = (scaled_proportion * slots_per_line as f64).ceil() as usize;
-            if slots_left == 0 {
-                // Always fill at least one slot
-                slots_left = 1;
+    columns.iter().enumerate().for_each(|(column, value)| {
+        let Some(value) = value else {
+            return;
+        };
+        let proportion = (value - min) / (max - min);
+        let scaled_proportion = proportion * height as f64;
+        let mut slots_left = (scaled_proportion * slots_per_line as f64).ceil() as usize;
+        if slots_left == 0 {
+            // Always fill at least one slot
+            slots_left = 1;
+        }
+        (0..height).for_each(|row| {
+            if slots_left > slots_per_line {
+                canvas[row][column] = *SPARKS.last().unwrap();
+                slots_left -= slots_per_line;
+            } else if slots_left > 0 {
+                canvas[row][column] = SPARKS[slots_left - 1];
+                slots_left = 0;
             }
-            (0..height).for_each(|row| {
-                if slots_left > slots_per_line {
-                    canvas[row][column] = *SPARKS.last().unwrap();
-                    slots_left -= slots_per_line;
-                } else if slots_left > 0 {
-                    canvas[row][column] = SPARKS[slots_left - 1];
-                    slots_left = 0;
-                }
-            })
-        });
+        })
+    });
 
     canvas
         .iter()
@@ -47,32 +220,122 @@ pub fn build_sparkline(timestamps: &[i64], width: usize, height: usize) -> Strin
         .unwrap()
 }
 
+/// The timestamps found in a log, and statistics about the lines that were scanned.
+#[derive(Debug, Default, PartialEq)]
+pub struct ScanResult {
+    /// Timestamps in nanoseconds since the UNIX epoch, in the order that they appear in the log.
+    pub timestamps: Vec<i64>,
+    /// The timestamps of each group, keyed by the text that the finder's group regex captured.
+    /// Empty unless the finder has one.
+    pub groups: HashMap<String, Vec<i64>>,
+    /// The timestamp and value of each line that the finder's value regex found a number on.
+    /// Empty unless the finder has one and keeps the values.
+    pub values: Vec<(i64, f64)>,
+    /// The same values, counted in sketches instead if the finder sketches them.
+    pub value_sketches: SketchTimeline,
+    pub stats: ScanStats,
+}
+
+impl ScanResult {
+    /// Combines the results of scanning consecutive parts of a log.
+    pub fn merge(mut self, mut other: ScanResult) -> ScanResult {
+        self.timestamps.append(&mut other.timestamps);
+        self.values.append(&mut other.values);
+        self.value_sketches = self.value_sketches.merge(other.value_sketches);
+        for (group, mut timestamps) in other.groups {
+            self.groups
+                .entry(group)
+                .or_default()
+                .append(&mut timestamps);
+        }
+        self.stats = self.stats.merge(other.stats);
+        self
+    }
+}
+
 pub fn scan_for_timestamps<R>(reader: R, format: &str) -> Result<Vec<i64>>
 where
     R: Read,
 {
     let date_finder = TimestampFinder::new(format)?;
-    let timestamps = BufReader::new(reader)
-        .lines()
-        .map_while(Result::ok)
-        .filter_map(|line| date_finder.find_timestamp(&line))
-        .collect();
-    Ok(timestamps)
+    Ok(scan_for_timestamps_with(reader, &date_finder)?.timestamps)
 }
 
+/// Scans each line that `reader` yields for a timestamp. Fails if reading fails, e.g. because a
+/// compressed log is corrupt, rather than returning what was read up to that point.
+pub fn scan_for_timestamps_with<R>(reader: R, date_finder: &TimestampFinder) -> Result<ScanResult>
+where
+    R: Read,
+{
+    let mut result = ScanResult::default();
+    let mut reader = BufReader::new(reader);
+    let mut buffer = vec![];
+    // Lines are read as bytes so that one with invalid UTF-8 doesn't end the scan
+    while reader.read_until(b'\n', &mut buffer)? > 0 {
+        let bytes = buffer.strip_suffix(b"\n").unwrap_or(&buffer);
+        let bytes = bytes.strip_suffix(b"\r").unwrap_or(bytes);
+        let line = match std::str::from_utf8(bytes) {
+            Ok(line) => Cow::Borrowed(line),
+            Err(_) => {
+                result.stats.invalid_utf8_lines += 1;
+                String::from_utf8_lossy(bytes)
+            }
+        };
+        let scanned = date_finder.scan_line(&line);
+        if let ScannedLine::Timestamp(timestamp) = scanned {
+            result.timestamps.push(timestamp);
+            if let Some(group) = date_finder.group(&line) {
+                match result.groups.get_mut(group) {
+                    Some(timestamps) => timestamps.push(timestamp),
+                    None => {
+                        result.groups.insert(group.to_string(), vec![timestamp]);
+                    }
+                }
+            }
+            if date_finder.reads_values() {
+                let value = date_finder.value(&line);
+                match value {
+                    Some(value) if date_finder.sketches_values() => {
+                        result.value_sketches.insert(timestamp, value)
+                    }
+                    Some(value) => result.values.push((timestamp, value)),
+                    None => {}
+                }
+                result.stats.record_value(value);
+            }
+        }
+        result.stats.record(&line, &scanned);
+        buffer.clear();
+    }
+    Ok(result)
+}
+
+/// Builds the header and footer that label points in time on the sparkline. Each label is as
+/// precise as it needs to be to tell adjacent markers apart, and shows the wall clock in `timezone`.
+/// If the sparkline is too narrow for `marker_count` labels, there are fewer markers, or none at
+/// all.
 pub fn build_time_markers(
     timestamps: &[i64],
     marker_count: usize,
     terminal_width: usize,
+    timezone: Timezone,
 ) -> (String, String) {
     if marker_count < 2 || timestamps.len() < 2 {
         return (String::from(""), String::from(""));
     }
+    let first_timestamp = timestamps.iter().min().unwrap();
+    let last_timestamp = timestamps.iter().max().unwrap();
+    let precision_for = |marker_count: usize| {
+        time_marker::Precision::for_spacing(
+            (last_timestamp - first_timestamp) / (marker_count - 1) as i64,
+        )
+    };
+    let fits = |count: usize| markers_fit(count, terminal_width, precision_for(count).width());
+    let Some(marker_count) = (2..=marker_count).rev().find(|count| fits(*count)) else {
+        return (String::from(""), String::from(""));
+    };
 
-    let mut footer_marker_count = marker_count / 2;
-    if footer_marker_count % 2 != 0 {
-        footer_marker_count += 1;
-    }
+    let footer_marker_count = footer_marker_count(marker_count);
 
     let marker_timestamp_offsets: Vec<usize> = (0..marker_count)
         .map(|i| (i as f64 * timestamps.len() as f64 / (marker_count - 1) as f64).ceil() as usize)
@@ -84,6 +347,8 @@ pub fn build_time_markers(
     let header_terminal_offsets = marker_terminal_offsets[footer_marker_count..].to_vec();
     let footer_terminal_offsets = marker_terminal_offsets[..footer_marker_count].to_vec();
 
+    let precision = precision_for(marker_count);
+
     let mut header_canvas =
         time_marker::Canvas::new(terminal_width, header_timestamp_offsets.len() + 1);
     let mut footer_canvas =
@@ -97,6 +362,8 @@ pub fn build_time_markers(
             timestamp: timestamps[*timestamp_index - 1],
             timestamp_location: time_marker::TimestampLocation::Top,
             vertical_offset: index + 1,
+            precision,
+            timezone,
         })
         .for_each(|time_marker| {
             if let Err(e) = time_marker.render(&mut header_canvas) {
@@ -112,6 +379,8 @@ pub fn build_time_markers(
             timestamp: timestamps[*timestamp_index],
             timestamp_location: time_marker::TimestampLocation::Bottom,
             vertical_offset: footer_timestamp_offsets.len() - index,
+            precision,
+            timezone,
         })
         .for_each(|time_marker| {
             if let Err(e) = time_marker.render(&mut footer_canvas) {
@@ -122,6 +391,37 @@ pub fn build_time_markers(
     (format!("{}", header_canvas), format!("{}", footer_canvas))
 }
 
+/// Splits the markers between the footer, which gets the ones on the left, and the header, which
+/// always gets at least the one on the right edge.
+fn footer_marker_count(marker_count: usize) -> usize {
+    let mut footer_marker_count = marker_count / 2;
+    if !footer_marker_count.is_multiple_of(2) {
+        footer_marker_count += 1;
+    }
+    footer_marker_count.min(marker_count - 1)
+}
+
+/// Whether `count` markers with labels that are `label_width` characters wide fit on a sparkline
+/// that's `terminal_width` columns wide. Labels in the footer extend to the right of their
+/// markers, and those in the header to the left, so it's the markers in the middle that need the
+/// room.
+fn markers_fit(count: usize, terminal_width: usize, label_width: usize) -> bool {
+    if terminal_width < label_width {
+        return false;
+    }
+    let footer_marker_count = footer_marker_count(count);
+    marker_offsets(count, terminal_width)
+        .iter()
+        .enumerate()
+        .all(|(index, offset)| {
+            if index < footer_marker_count {
+                offset + label_width <= terminal_width
+            } else {
+                offset + 1 >= label_width
+            }
+        })
+}
+
 fn marker_offsets(count: usize, terminal_width: usize) -> Vec<usize> {
     // Always show a marker at the left edge
     let mut offsets = vec![0];
@@ -140,24 +440,111 @@ fn marker_offsets(count: usize, terminal_width: usize) -> Vec<usize> {
     offsets
 }
 
-fn timestamp_frequency_distribution(timestamps: &[i64], bucket_count: usize) -> Vec<usize> {
-    let first_timestamp = timestamps.iter().min().unwrap();
-    let last_timestamp = timestamps.iter().max().unwrap();
-    let duration_seconds = last_timestamp - first_timestamp;
-    let seconds_per_bucket = duration_seconds as f64 / bucket_count as f64;
+fn time_range(timestamps: &[i64]) -> (i64, i64) {
+    (
+        *timestamps.iter().min().unwrap(),
+        *timestamps.iter().max().unwrap(),
+    )
+}
 
+fn timestamp_frequency_distribution(
+    timestamps: &[i64],
+    range: (i64, i64),
+    bucket_count: usize,
+) -> Vec<usize> {
+    frequency_distribution(timestamps, bucket_count, |timestamp| {
+        column_in_range(timestamp, range, bucket_count)
+    })
+}
+
+fn frequency_distribution(
+    timestamps: &[i64],
+    bucket_count: usize,
+    bucket_of: impl Fn(i64) -> Option<usize>,
+) -> Vec<usize> {
     let mut timestamps_per_bucket = vec![0; bucket_count];
     for timestamp in timestamps {
-        let bucket_index = usize::min(
-            ((timestamp - first_timestamp) as f64 / seconds_per_bucket) as usize,
-            bucket_count - 1,
-        );
-        timestamps_per_bucket[bucket_index] += 1;
+        if let Some(bucket_index) = bucket_of(*timestamp) {
+            timestamps_per_bucket[bucket_index] += 1;
+        }
     }
 
     timestamps_per_bucket
 }
 
+fn value_distribution(
+    values: &[(i64, f64)],
+    aggregate: Aggregate,
+    bucket_count: usize,
+    bucket_of: impl Fn(i64) -> Option<usize>,
+) -> Vec<Option<f64>> {
+    let mut values_per_bucket = vec![vec![]; bucket_count];
+    for (timestamp, value) in values {
+        if let Some(bucket_index) = bucket_of(*timestamp) {
+            values_per_bucket[bucket_index].push(*value);
+        }
+    }
+
+    values_per_bucket
+        .iter_mut()
+        .map(|values| aggregate.apply(values))
+        .collect()
+}
+
+fn render_percentiles(
+    sketches: &[QuantileSketch],
+    percentiles: &[f64],
+    height: usize,
+) -> Vec<String> {
+    let columns: Vec<Vec<Option<f64>>> = percentiles
+        .iter()
+        .map(|percentile| {
+            sketches
+                .iter()
+                .map(|sketch| sketch.percentile(*percentile))
+                .collect()
+        })
+        .collect();
+    render_sparklines(&columns, height)
+}
+
+/// Finds which of `bucket_count` equal parts of `range` a timestamp falls into.
+fn column_in_range(
+    timestamp: i64,
+    (first_timestamp, last_timestamp): (i64, i64),
+    bucket_count: usize,
+) -> Option<usize> {
+    if !(first_timestamp..=last_timestamp).contains(&timestamp) {
+        return None;
+    }
+    let duration = last_timestamp - first_timestamp;
+    let nanos_per_column = duration as f64 / bucket_count as f64;
+    Some(usize::min(
+        ((timestamp - first_timestamp) as f64 / nanos_per_column) as usize,
+        bucket_count - 1,
+    ))
+}
+
+/// Finds which of `bucket_count` buckets of `bucket_size` nanoseconds, from the one that starts at
+/// `first_bucket`, a timestamp falls into.
+fn column_in_buckets(
+    timestamp: i64,
+    first_bucket: i64,
+    bucket_count: usize,
+    bucket_size: i64,
+) -> Option<usize> {
+    usize::try_from((timestamp - first_bucket).div_euclid(bucket_size))
+        .ok()
+        .filter(|index| *index < bucket_count)
+}
+
+fn counts(frequencies: Vec<usize>) -> Vec<Option<f64>> {
+    frequencies
+        .into_iter()
+        .map(|frequency| Some(frequency as f64))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -230,6 +617,106 @@ e4ff7f1d9d80f HTTP/1.1\"
         );
     }
 
+    #[test]
+    fn build_sparklines_shares_axis_and_scale() {
+        let busy = [0, 1, 2, 3, 4, 5, 6, 7];
+        let quiet = [4];
+        let sparklines = build_sparklines(&[&busy, &quiet, &[]], 4, 1);
+        assert_eq!(sparklines, ["████", "▁▁▄▁", "▁▁▁▁"]);
+
+        let sparklines = build_sparklines(&[&busy, &quiet], 4, 2);
+        assert_eq!(sparklines, ["████\n████", "    \n▁▁█▁"]);
+
+        let sparklines = build_sparklines_in_range(&[&busy, &quiet], (4, 11), 4, 1);
+        assert_eq!(sparklines, ["██▁▁", "▄▁▁▁"]);
+        let sparklines = build_sparklines_in_range(&[&[]], (4, 11), 4, 1);
+        assert_eq!(sparklines, ["▁▁▁▁"]);
+    }
+
+    #[test]
+    fn build_sparklines_in_buckets_() {
+        const MINUTE: i64 = 60_000_000_000;
+        let timestamps = [MINUTE, MINUTE + 1, 2 * MINUTE - 1, 3 * MINUTE, 5 * MINUTE];
+        let sparklines = build_sparklines_in_buckets(&[&timestamps], MINUTE, 3, MINUTE, 1);
+        assert_eq!(sparklines, ["█▁▃"]);
+
+        let sparklines =
+            build_sparklines_in_buckets(&[&timestamps, &[2 * MINUTE]], 2 * MINUTE, 2, MINUTE, 1);
+        assert_eq!(sparklines, ["▁█", "█▁"]);
+    }
+
+    #[test]
+    fn build_value_sparklines_() {
+        const MINUTE: i64 = 60_000_000_000;
+        let values = [
+            (MINUTE, 10.0),
+            (MINUTE + 1, 30.0),
+            (2 * MINUTE, 5.0),
+            (5 * MINUTE, 99.0),
+        ];
+        let sparklines =
+            build_value_sparklines_in_buckets(&[&values], Aggregate::Avg, MINUTE, 3, MINUTE, 1);
+        assert_eq!(sparklines, ["█▁ "]);
+
+        let sparklines = build_value_sparklines_in_range(
+            &[&values, &[(3 * MINUTE, 20.0)]],
+            Aggregate::Max,
+            (MINUTE, 4 * MINUTE),
+            3,
+            2,
+        );
+        assert_eq!(sparklines, ["█  \n█▁ ", "  ▂\n  █"]);
+    }
+
+    #[test]
+    fn build_percentile_sparklines_() {
+        const MINUTE: i64 = 60_000_000_000;
+        let mut values = SketchTimeline::new();
+        (1..=100).for_each(|i| values.insert(MINUTE, i as f64));
+        (1..=100).for_each(|i| values.insert(2 * MINUTE, 20.0 + i as f64 / 10.0));
+        let sparklines =
+            build_percentile_sparklines_in_buckets(&values, &[99.0, 50.0], MINUTE, 3, MINUTE, 1);
+        assert_eq!(sparklines, ["█▁ ", "▃▁ "]);
+
+        let sparklines =
+            build_percentile_sparklines_in_range(&values, &[50.0], (MINUTE, 2 * MINUTE), 2, 1);
+        assert_eq!(sparklines, ["█▁"]);
+    }
+
+    #[test]
+    fn bucket_start_() {
+        const HOUR: i64 = 3_600_000_000_000;
+        const DAY: i64 = 24 * HOUR;
+        let utc = Timezone::Utc;
+        assert_eq!(bucket_start(0, HOUR, utc), 0);
+        assert_eq!(bucket_start(HOUR - 1, HOUR, utc), 0);
+        assert_eq!(bucket_start(5 * HOUR + 7, HOUR, utc), 5 * HOUR);
+        assert_eq!(bucket_start(-1, HOUR, utc), -HOUR);
+
+        // India is five and a half hours ahead of UTC, so its hours start at half past in UTC
+        let kolkata: Timezone = "Asia/Kolkata".parse().unwrap();
+        assert_eq!(
+            bucket_start(5 * HOUR + 7, HOUR, kolkata),
+            4 * HOUR + HOUR / 2
+        );
+        assert_eq!(
+            bucket_start(5 * HOUR + HOUR / 2, HOUR, kolkata),
+            5 * HOUR + HOUR / 2
+        );
+
+        // 2024-01-02 03:04:05 UTC is still January 1st in New York, where the day started at
+        // 05:00 UTC, and in the summer, days start an hour earlier
+        let new_york: Timezone = "America/New_York".parse().unwrap();
+        assert_eq!(
+            bucket_start(1_704_164_645_000_000_000, DAY, new_york),
+            1_704_085_200_000_000_000
+        );
+        assert_eq!(
+            bucket_start(1_719_835_200_000_000_000, DAY, new_york),
+            1_719_806_400_000_000_000
+        );
+    }
+
     #[test]
     fn build_time_markers_even() {
         let log = "Nov 23 06:26:40 ip-10-1-1-1 haproxy[20128]: 10.1.1.10:57305 [23/Nov/2019:06:26:40.781] public myapp/i-05fa49c0e7db8c328 0/0/0/78/78 206 913/458 - - ---- 9/9/6/0/0 0/0 {bytes=0-0} {||1|bytes 0-0/499704} \"GET \
@@ -255,7 +742,7 @@ e4ff7f1d9d80f HTTP/1.1\"
 ";
         let format = "%d/%b/%Y:%H:%M:%S%.f";
         let timestamps = scan_for_timestamps(log.as_bytes(), format).unwrap();
-        let (header, footer) = build_time_markers(&timestamps, 8, 80);
+        let (header, footer) = build_time_markers(&timestamps, 8, 80, Timezone::Utc);
         assert_eq!(
             header,
             "                                                             2019-11-23 06:35:49
@@ -301,7 +788,7 @@ e4ff7f1d9d80f HTTP/1.1\"
 ";
         let format = "%d/%b/%Y:%H:%M:%S%.f";
         let timestamps = scan_for_timestamps(log.as_bytes(), format).unwrap();
-        let (header, footer) = build_time_markers(&timestamps, 15, 80);
+        let (header, footer) = build_time_markers(&timestamps, 15, 80, Timezone::Utc);
         assert_eq!(
             header,
             "                                                             2019-11-23 06:35:49
@@ -329,13 +816,89 @@ e4ff7f1d9d80f HTTP/1.1\"
         );
     }
 
+    #[test]
+    fn build_time_markers_sub_second() {
+        let log = "2024-01-02T03:04:05.100Z GET /
+2024-01-02T03:04:05.250Z GET /
+2024-01-02T03:04:05.400Z GET /
+2024-01-02T03:04:05.650Z GET /
+2024-01-02T03:04:05.900Z GET /
+2024-01-02T03:04:06.300Z GET /
+";
+        let timestamps = scan_for_timestamps(log.as_bytes(), "rfc3339").unwrap();
+        let sparkline = build_sparkline(&timestamps, 12, 1);
+        assert_eq!(sparkline, "██▁█▁█▁▁█▁▁█");
+        let (header, footer) = build_time_markers(&timestamps, 4, 60, Timezone::Utc);
+        assert_eq!(
+            header,
+            "                                     2024-01-02 03:04:06.300
+                 2024-01-02 03:04:05.650                   |
+                                       |                   |
+"
+        );
+        assert_eq!(
+            footer,
+            "|                   |                                       
+|                   2024-01-02 03:04:05.400                 
+2024-01-02 03:04:05.100                                     
+"
+        );
+    }
+
+    #[test]
+    fn build_time_markers_narrow() {
+        const MINUTE: i64 = 60_000_000_000;
+        let timestamps: Vec<i64> = (0..100).map(|i| i * MINUTE).collect();
+
+        // There's only room for four of the markers
+        let (header, footer) = build_time_markers(&timestamps, 12, 30, Timezone::Utc);
+        assert_eq!(
+            header,
+            "           1970-01-01 01:39:00
+ 1970-01-01 01:06:00         |
+                   |         |
+"
+        );
+        assert_eq!(
+            footer,
+            "|         |                   
+|         1970-01-01 00:34:00 
+1970-01-01 00:00:00           
+"
+        );
+
+        // And for none at all
+        let (header, footer) = build_time_markers(&timestamps, 12, 18, Timezone::Utc);
+        assert_eq!(header, "");
+        assert_eq!(footer, "");
+    }
+
+    #[test]
+    fn build_time_markers_two() {
+        const MINUTE: i64 = 60_000_000_000;
+        let timestamps: Vec<i64> = (0..100).map(|i| i * MINUTE).collect();
+        let (header, footer) = build_time_markers(&timestamps, 2, 40, Timezone::Utc);
+        assert_eq!(
+            header,
+            "                     1970-01-01 01:39:00
+                                       |
+"
+        );
+        assert_eq!(
+            footer,
+            "|                                       
+1970-01-01 00:00:00                     
+"
+        );
+    }
+
     #[test]
     fn build_time_markers_too_few_timestamps() {
         let log = "Nov 23 06:26:40 ip-10-1-1-1 haproxy[20128]: 10.1.1.10:57305 [23/Nov/2019:06:26:40.781] public myapp/i-05fa49c0e7db8c328 0/0/0/78/78 206 913/458 - - ---- 9/9/6/0/0 0/0 {bytes=0-0} {||1|bytes 0-0/499704} \"GET \
         /2518cb13a48bdf53b2f936f44e7042a3cc7baa06 HTTP/1.1\"\n";
         let format = "%d/%b/%Y:%H:%M:%S%.f";
         let timestamps = scan_for_timestamps(log.as_bytes(), format).unwrap();
-        let (header, footer) = build_time_markers(&timestamps, 8, 80);
+        let (header, footer) = build_time_markers(&timestamps, 8, 80, Timezone::Utc);
         assert_eq!(header, "");
         assert_eq!(footer, "");
     }
@@ -347,18 +910,191 @@ Nov 23 14:21:53 ip-10-1-26-81 haproxy[20128]: 54.209.125.72:58030 [23/Nov/2019:1
 ";
         let format = "%d/%b/%Y:%H:%M:%S%.f";
         let timestamps = scan_for_timestamps(log.as_bytes(), format).unwrap();
-        assert_eq!(timestamps, [1574490400, 1574518913]);
+        assert_eq!(
+            timestamps,
+            [1_574_490_400_781_000_000, 1_574_518_913_241_000_000]
+        );
+    }
+
+    #[test]
+    fn scan_for_timestamps_without_year() {
+        let log = "Nov 23 06:26:40 ip-10-1-26-81 haproxy[20128]: 54.242.135.245:57305 [23/Nov/2019:06:26:40.781] public repackager/i-05fa49c0e7db8c328 0/0/0/78/78 206 913/458 - - ---- 9/9/6/0/0 0/0 {1.1 v1-akamaitech.net(ghost) (AkamaiGHost), 1.1 v1-akamaitech.net(ghost) (AkamaiGHost), 1.1 akamai.n|bytes=0-0} {||1|bytes 0-0/499704} \"GET /deliveries/2518cb13a48bdf53b2f936f44e7042a3cc7baa06.m3u8/seg-88-v1-a1.ts HTTP/1.1\"
+Nov 23 14:21:53 ip-10-1-26-81 haproxy[20128]: 54.209.125.72:58030 [23/Nov/2019:14:21:53.241] public repackager/i-0728dc03214405429 0/0/0/246/246 200 810/8324 - - ---- 17/17/12/0/0 0/0 {1.1 v1-akamaitech.net(ghost) (AkamaiGHost), 1.1 v1-akamaitech.net(ghost) (AkamaiGHost), 1.1 akamai.n|} {||7870|} \"GET /deliveries/4fb7b6ff75f8a13da4ac482e25e29790105ba075.m3u8?origin_v2=1 HTTP/1.1\"
+";
+        let date_finder = TimestampFinder::new("syslog")
+            .unwrap()
+            .with_reference_date(chrono::NaiveDate::from_ymd_opt(2019, 12, 31).unwrap());
+        let timestamps = scan_for_timestamps_with(log.as_bytes(), &date_finder)
+            .unwrap()
+            .timestamps;
+        assert_eq!(
+            timestamps,
+            [1_574_490_400_000_000_000, 1_574_518_913_000_000_000]
+        );
+    }
+
+    #[test]
+    fn scan_for_timestamps_json_field() {
+        let log = r#"{"level":"info","ts":"2024-01-02T03:04:05Z","user":{"created_at":"2019-11-23T06:26:40Z"}}
+{"level":"info","ts":1704164706,"msg":"numeric"}
+panic: runtime error: index out of range
+{"level":"info","msg":"no timestamp"}
+{"level":"info","ts":"2024-01-02T03:06:07.5Z","msg":"truncated"
+"#;
+        let date_finder = TimestampFinder::new("rfc3339")
+            .unwrap()
+            .with_extractor(Extractor::json_field("ts"));
+        let result = scan_for_timestamps_with(log.as_bytes(), &date_finder).unwrap();
+        assert_eq!(
+            result.timestamps,
+            [1_704_164_645_000_000_000, 1_704_164_706_000_000_000]
+        );
+        assert_eq!(result.stats.malformed_lines, 2);
+    }
+
+    #[test]
+    fn scan_for_timestamps_read_error() {
+        struct FailingReader;
+        impl Read for FailingReader {
+            fn read(&mut self, _: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("corrupt deflate stream"))
+            }
+        }
+
+        let log = "[23/Nov/2019:06:26:40 +0000] GET /\n";
+        let date_finder = TimestampFinder::new("clf").unwrap();
+        let error = scan_for_timestamps_with(log.as_bytes().chain(FailingReader), &date_finder)
+            .unwrap_err();
+        assert_eq!(error.to_string(), "corrupt deflate stream");
+    }
+
+    #[test]
+    fn scan_for_timestamps_invalid_utf8() {
+        let mut log = b"[23/Nov/2019:06:26:40 +0000] GET /\r\n".to_vec();
+        log.extend_from_slice(b"[23/Nov/2019:06:26:41 +0000] GET /\xff\xfe\x00binary\n");
+        log.extend_from_slice(b"\xc3\x28\n");
+        log.extend_from_slice(b"[23/Nov/2019:06:26:42 +0000] GET /");
+        let date_finder = TimestampFinder::new("clf").unwrap();
+        let result = scan_for_timestamps_with(log.as_slice(), &date_finder).unwrap();
+        assert_eq!(
+            result.timestamps,
+            [
+                1_574_490_400_000_000_000,
+                1_574_490_401_000_000_000,
+                1_574_490_402_000_000_000
+            ]
+        );
+        assert_eq!(result.stats.invalid_utf8_lines, 2);
+    }
+
+    #[test]
+    fn scan_for_timestamps_stats() {
+        let log = "[23/Nov/2019:06:26:41 +0000] GET /
+[23/Nov/2019:06:26:40 +0000] GET /
+[31/Nov/2019:06:26:42 +0000] GET /
+GET /favicon.ico
+";
+        let date_finder = TimestampFinder::new("clf").unwrap();
+        let stats = scan_for_timestamps_with(log.as_bytes(), &date_finder)
+            .unwrap()
+            .stats;
+        assert_eq!(
+            stats,
+            ScanStats {
+                total_lines: 4,
+                matched_lines: 2,
+                unparseable_lines: 1,
+                first_timestamp: Some(1_574_490_400_000_000_000),
+                last_timestamp: Some(1_574_490_401_000_000_000),
+                unmatched_samples: vec![
+                    "[31/Nov/2019:06:26:42 +0000] GET /".to_string(),
+                    "GET /favicon.ico".to_string()
+                ],
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn scan_for_timestamps_groups() {
+        let log = "[23/Nov/2019:06:26:40 +0000] GET / 200
+[23/Nov/2019:06:26:41 +0000] GET / 503
+[23/Nov/2019:06:26:42 +0000] GET / 200
+[23/Nov/2019:06:26:43 +0000] starting up
+";
+        let date_finder = TimestampFinder::new("clf")
+            .unwrap()
+            .with_group_by(regex::Regex::new(r" (\d)\d\d$").unwrap());
+        let result = scan_for_timestamps_with(&log.as_bytes()[..78], &date_finder)
+            .unwrap()
+            .merge(scan_for_timestamps_with(&log.as_bytes()[78..], &date_finder).unwrap());
+        assert_eq!(result.timestamps.len(), 4);
+        assert_eq!(
+            result.groups,
+            HashMap::from([
+                (
+                    String::from("2"),
+                    vec![1_574_490_400_000_000_000, 1_574_490_402_000_000_000]
+                ),
+                (String::from("5"), vec![1_574_490_401_000_000_000]),
+            ])
+        );
+    }
+
+    #[test]
+    fn scan_for_timestamps_values() {
+        let log = "[23/Nov/2019:06:26:40 +0000] GET / 200 took 12ms
+[23/Nov/2019:06:26:41 +0000] GET / 503
+[23/Nov/2019:06:26:42 +0000] GET / 200 took 7.5ms
+starting up, took 2ms
+";
+        let date_finder = TimestampFinder::new("clf")
+            .unwrap()
+            .with_value(regex::Regex::new(r"took ([\d.]+)ms").unwrap());
+        let result = scan_for_timestamps_with(log.as_bytes(), &date_finder).unwrap();
+        assert_eq!(
+            result.values,
+            [
+                (1_574_490_400_000_000_000, 12.0),
+                (1_574_490_402_000_000_000, 7.5)
+            ]
+        );
+        assert_eq!(result.stats.missing_value_lines, 1);
+    }
+
+    #[test]
+    fn scan_for_timestamps_sketched_values() {
+        let log = "[23/Nov/2019:06:26:40 +0000] GET / 200 took 12ms
+[23/Nov/2019:06:26:41 +0000] GET / 503
+[23/Nov/2019:06:26:42 +0000] GET / 200 took 7.5ms
+";
+        let date_finder = TimestampFinder::new("clf")
+            .unwrap()
+            .with_value(regex::Regex::new(r"took ([\d.]+)ms").unwrap())
+            .with_sketched_values(true);
+        let result = scan_for_timestamps_with(&log.as_bytes()[..88], &date_finder)
+            .unwrap()
+            .merge(scan_for_timestamps_with(&log.as_bytes()[88..], &date_finder).unwrap());
+        assert!(result.values.is_empty());
+        let mut expected = SketchTimeline::new();
+        expected.insert(1_574_490_400_000_000_000, 12.0);
+        expected.insert(1_574_490_402_000_000_000, 7.5);
+        assert_eq!(result.value_sketches, expected);
+        assert_eq!(result.stats.missing_value_lines, 1);
     }
 
     #[test]
     fn timestamp_frequency_distribution_() {
         let timestamps = vec![1, 2, 3, 4, 5];
-        let bins = timestamp_frequency_distribution(&timestamps, 5);
+        let bins = timestamp_frequency_distribution(&timestamps, (1, 5), 5);
         assert_eq!(bins, [1, 1, 1, 1, 1]);
 
         let timestamps = vec![1, 2, 3, 4, 5, 6];
-        let bins = timestamp_frequency_distribution(&timestamps, 3);
+        let bins = timestamp_frequency_distribution(&timestamps, (1, 6), 3);
         assert_eq!(bins, [2, 2, 2]);
+
+        let bins = timestamp_frequency_distribution(&timestamps, (1, 12), 3);
+        assert_eq!(bins, [4, 2, 0]);
     }
 
     #[test]
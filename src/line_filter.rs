@@ -0,0 +1,63 @@
+use anyhow::Result;
+use regex::RegexSet;
+
+/// Picks the lines to scan with regexes, like `grep -e` and `grep -v -e`. A line is kept if it
+/// matches any of the matching regexes, or there are none, and it matches none of the excluding
+/// ones.
+#[derive(Clone, Debug, Default)]
+pub struct LineFilter {
+    matching: Option<RegexSet>,
+    excluding: Option<RegexSet>,
+}
+
+impl LineFilter {
+    pub fn new<S: AsRef<str>>(matching: &[S], excluding: &[S]) -> Result<Self> {
+        let compile = |patterns: &[S]| -> Result<Option<RegexSet>> {
+            if patterns.is_empty() {
+                Ok(None)
+            } else {
+                Ok(Some(RegexSet::new(patterns)?))
+            }
+        };
+        Ok(LineFilter {
+            matching: compile(matching)?,
+            excluding: compile(excluding)?,
+        })
+    }
+
+    pub fn accepts(&self, line: &str) -> bool {
+        self.matching.as_ref().is_none_or(|set| set.is_match(line))
+            && !self
+                .excluding
+                .as_ref()
+                .is_some_and(|set| set.is_match(line))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn line_filter_accepts() {
+        let filter = LineFilter::default();
+        assert!(filter.accepts("GET /"));
+
+        let filter = LineFilter::new(&["GET", "POST"], &[]).unwrap();
+        assert!(filter.accepts("GET /"));
+        assert!(filter.accepts("POST /login"));
+        assert!(!filter.accepts("DELETE /"));
+
+        let filter = LineFilter::new(&[], &[r"\.png", "healthcheck"]).unwrap();
+        assert!(filter.accepts("GET /"));
+        assert!(!filter.accepts("GET /logo.png"));
+        assert!(!filter.accepts("GET /healthcheck"));
+
+        let filter = LineFilter::new(&["GET"], &["healthcheck"]).unwrap();
+        assert!(filter.accepts("GET /"));
+        assert!(!filter.accepts("GET /healthcheck"));
+        assert!(!filter.accepts("POST /"));
+
+        assert!(LineFilter::new(&["("], &[]).is_err());
+    }
+}
@@ -0,0 +1,183 @@
+/// A named timestamp format for a common source of logs.
+#[derive(Debug, PartialEq)]
+pub struct Preset {
+    /// Name that can be passed instead of a strftime-style format, e.g. "clf".
+    pub name: &'static str,
+    /// Where logs in this format usually come from.
+    pub description: &'static str,
+    /// The strftime-style format, or a UNIX timestamp format like "epoch_ms". Literal text around
+    /// the timestamp (like the brackets in `clf`) keeps other dates on the line from being matched.
+    pub format: &'static str,
+    /// A typical log line.
+    pub sample: &'static str,
+}
+
+/// Built-in presets. Format detection tries them in this order and prefers the earlier one when
+/// two match equally well, so more specific formats come before looser ones.
+pub const PRESETS: &[Preset] = &[
+    Preset {
+        name: "clf",
+        description: "Common Log Format (Apache and most web servers)",
+        format: "[%d/%b/%Y:%H:%M:%S%.f %z]",
+        sample: r#"127.0.0.1 - frank [10/Oct/2000:13:55:36 -0700] "GET /apache_pb.gif HTTP/1.0" 200 2326"#,
+    },
+    Preset {
+        name: "nginx",
+        description: "nginx access log (combined format)",
+        format: "[%d/%b/%Y:%H:%M:%S %z]",
+        sample: r#"10.1.1.10 - - [23/Nov/2019:06:26:40 +0000] "GET / HTTP/1.1" 200 612 "-" "curl/7.64.1""#,
+    },
+    Preset {
+        name: "haproxy",
+        description: "HAProxy HTTP log (accept date)",
+        format: "[%d/%b/%Y:%H:%M:%S%.f]",
+        sample: r#"Nov 23 06:26:40 ip-10-1-1-1 haproxy[20128]: 10.1.1.10:57305 [23/Nov/2019:06:26:40.781] public myapp/i-05fa49c0e7db8c328 0/0/0/78/78 206 913/458 - - ---- 9/9/6/0/0 0/0 "GET / HTTP/1.1""#,
+    },
+    Preset {
+        name: "docker",
+        description: "Docker json-file logging driver",
+        format: r#""time":"%+""#,
+        sample: r#"{"log":"started\n","stream":"stdout","time":"2024-01-02T03:04:05.123456789Z"}"#,
+    },
+    Preset {
+        name: "rfc5424",
+        description: "Syslog (RFC 5424)",
+        format: ">1 %+",
+        sample: "<165>1 2003-10-11T22:14:15.003Z mymachine.example.com evntslog - ID47 - An application event",
+    },
+    Preset {
+        name: "rfc3339",
+        description: "RFC 3339, i.e. ISO 8601 with a UTC offset or Z",
+        format: "%+",
+        sample: r#"{"level":"info","ts":"2024-01-02T03:04:05.123+01:00","msg":"started"}"#,
+    },
+    Preset {
+        name: "journald",
+        description: "journalctl with -o short-iso or -o short-iso-precise",
+        format: "%Y-%m-%dT%H:%M:%S%.f%z",
+        sample: "2024-01-02T03:04:05+0100 myhost systemd[1]: Started Session 1 of user root.",
+    },
+    Preset {
+        name: "iso8601",
+        description: "ISO 8601 without a UTC offset",
+        format: "%Y-%m-%dT%H:%M:%S%.f",
+        sample: "2024-01-02T03:04:05.123 INFO started",
+    },
+    Preset {
+        name: "go",
+        description: "Go's time.Time.String",
+        format: "%Y-%m-%d %H:%M:%S%.f %z",
+        sample: "2024-01-02 03:04:05.123456789 +0000 UTC m=+0.000034792 started",
+    },
+    Preset {
+        name: "log4j",
+        description: "Java log4j and logback, Python logging",
+        format: "%Y-%m-%d %H:%M:%S,%3f",
+        sample: "2024-01-02 03:04:05,123 INFO  [main] com.example.App - started",
+    },
+    Preset {
+        name: "iso8601-space",
+        description: "ISO 8601 with a space instead of a T, without a UTC offset",
+        format: "%Y-%m-%d %H:%M:%S%.f",
+        sample: "2024-01-02 03:04:05 started",
+    },
+    Preset {
+        name: "nginx-error",
+        description: "nginx error log, Go's log package",
+        format: "%Y/%m/%d %H:%M:%S%.f",
+        sample: "2024/01/02 03:04:05 [error] 1234#0: *5 open() failed",
+    },
+    Preset {
+        name: "java",
+        description: "Java's Date.toString",
+        format: "%a %b %d %H:%M:%S %Z %Y",
+        sample: "Tue Jan 02 03:04:05 UTC 2024 started",
+    },
+    Preset {
+        name: "ctime",
+        description: "C's ctime, Apache error log",
+        format: "%a %b %e %H:%M:%S%.f %Y",
+        sample: "[Tue Jan 02 03:04:05.123456 2024] [core:error] [pid 1234] AH00126",
+    },
+    Preset {
+        name: "k8s",
+        description: "Kubernetes components and other users of klog. The year is inferred.",
+        format: "%m%d %H:%M:%S%.f",
+        sample: "I0102 03:04:05.123456       1 controller.go:42] Starting controller",
+    },
+    Preset {
+        name: "syslog",
+        description: "Syslog (RFC 3164), e.g. /var/log/messages. The year is inferred.",
+        format: "%b %e %H:%M:%S",
+        sample: "Nov 23 06:26:40 ip-10-1-1-1 sshd[20128]: Accepted publickey for root from 10.1.1.10",
+    },
+    Preset {
+        name: "epoch_ns",
+        description: "UNIX timestamp in nanoseconds (Go, OpenTelemetry)",
+        format: "epoch_ns",
+        sample: r#"{"ts":1704164645123456789,"msg":"started"}"#,
+    },
+    Preset {
+        name: "epoch_us",
+        description: "UNIX timestamp in microseconds",
+        format: "epoch_us",
+        sample: "1704164645123456 10.1.1.10 GET / 200",
+    },
+    Preset {
+        name: "epoch_ms",
+        description: "UNIX timestamp in milliseconds (Java, JavaScript, Kafka)",
+        format: "epoch_ms",
+        sample: "1704164645123 10.1.1.10 GET / 200",
+    },
+    Preset {
+        name: "epoch_frac",
+        description: "UNIX timestamp in seconds with a fractional part (Squid, Zeek)",
+        format: "epoch_frac",
+        sample: "1704164645.123 10.1.1.10 TCP_MISS/200 GET /",
+    },
+    Preset {
+        name: "epoch",
+        description: "UNIX timestamp in seconds",
+        format: "%s",
+        sample: "1704164645 10.1.1.10 GET / 200",
+    },
+];
+
+/// Looks up a preset by name.
+pub fn find_preset(name: &str) -> Option<&'static Preset> {
+    PRESETS.iter().find(|preset| preset.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timestamp_finder::TimestampFinder;
+
+    #[test]
+    fn presets_match_their_samples() {
+        for preset in PRESETS {
+            let date_finder = TimestampFinder::new(preset.format).unwrap();
+            assert!(
+                date_finder.find_timestamp(preset.sample).is_some(),
+                "preset '{}' doesn't match its sample",
+                preset.name
+            );
+        }
+    }
+
+    #[test]
+    fn presets_have_unique_names() {
+        for (i, preset) in PRESETS.iter().enumerate() {
+            assert!(PRESETS[..i].iter().all(|other| other.name != preset.name));
+        }
+    }
+
+    #[test]
+    fn find_preset_() {
+        assert_eq!(
+            find_preset("clf").unwrap().format,
+            "[%d/%b/%Y:%H:%M:%S%.f %z]"
+        );
+        assert_eq!(find_preset("%d/%b/%Y"), None);
+    }
+}
@@ -0,0 +1,373 @@
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+
+/// How far a quantile may be from the true value, relative to it.
+const RELATIVE_ACCURACY: f64 = 0.01;
+
+/// Most slots of time that a [`SketchTimeline`] keeps a sketch for.
+const MAX_SLOTS: usize = 4096;
+
+/// Values closer to zero than this are counted as zero, since their logarithms aren't useful.
+const MIN_INDEXABLE_VALUE: f64 = 1e-9;
+
+/// Estimates quantiles of a stream of values without keeping them, in the way of DDSketch. Each
+/// value is counted in a bucket whose bounds grow exponentially, so that any quantile is within 1%
+/// of the true value, however the values are spread.
+#[derive(Clone, Debug, PartialEq)]
+pub struct QuantileSketch {
+    /// Logarithm of the ratio between the bounds of a bucket
+    gamma_ln: f64,
+    /// Numbers of positive values, by bucket
+    positive: BTreeMap<i32, u64>,
+    /// Numbers of negative values, by the bucket of their magnitude
+    negative: BTreeMap<i32, u64>,
+    zeros: u64,
+    count: u64,
+    min: f64,
+    max: f64,
+}
+
+impl QuantileSketch {
+    pub fn new() -> QuantileSketch {
+        let gamma = (1.0 + RELATIVE_ACCURACY) / (1.0 - RELATIVE_ACCURACY);
+        QuantileSketch {
+            gamma_ln: gamma.ln(),
+            positive: BTreeMap::new(),
+            negative: BTreeMap::new(),
+            zeros: 0,
+            count: 0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    /// Counts a value. Values that aren't finite are ignored.
+    pub fn insert(&mut self, value: f64) {
+        if !value.is_finite() {
+            return;
+        }
+        if value >= MIN_INDEXABLE_VALUE {
+            *self.positive.entry(self.index(value)).or_default() += 1;
+        } else if value <= -MIN_INDEXABLE_VALUE {
+            *self.negative.entry(self.index(-value)).or_default() += 1;
+        } else {
+            self.zeros += 1;
+        }
+        self.count += 1;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    /// Number of values counted.
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Adds the values counted in `other`, as if they had been inserted into this sketch.
+    pub fn merge(&mut self, other: &QuantileSketch) {
+        for (index, count) in &other.positive {
+            *self.positive.entry(*index).or_default() += count;
+        }
+        for (index, count) in &other.negative {
+            *self.negative.entry(*index).or_default() += count;
+        }
+        self.zeros += other.zeros;
+        self.count += other.count;
+        self.min = self.min.min(other.min);
+        self.max = self.max.max(other.max);
+    }
+
+    /// Estimates the value that `percentile` percent of the others are at or below, like 99 for
+    /// p99. Returns `None` if no values were counted.
+    pub fn percentile(&self, percentile: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+        // The nearest rank, as in `Aggregate::Percentile`
+        let rank = (percentile / 100.0 * self.count as f64).ceil() as u64;
+        let rank = rank.clamp(1, self.count);
+        // The extremes are known exactly
+        if rank == 1 {
+            return Some(self.min);
+        }
+        if rank == self.count {
+            return Some(self.max);
+        }
+
+        let mut seen = 0;
+        let negative = self
+            .negative
+            .iter()
+            .rev()
+            .map(|(i, n)| (-self.value(*i), *n));
+        let zeros = std::iter::once((0.0, self.zeros));
+        let positive = self.positive.iter().map(|(i, n)| (self.value(*i), *n));
+        negative
+            .chain(zeros)
+            .chain(positive)
+            .find(|(_, n)| {
+                seen += n;
+                seen >= rank
+            })
+            .map(|(value, _)| value.clamp(self.min, self.max))
+    }
+
+    /// Finds the bucket of a positive value.
+    fn index(&self, value: f64) -> i32 {
+        (value.ln() / self.gamma_ln).ceil() as i32
+    }
+
+    /// Estimates the values in a bucket by the point that's equally far from both of its bounds,
+    /// relative to them.
+    fn value(&self, index: i32) -> f64 {
+        let upper_bound = (index as f64 * self.gamma_ln).exp();
+        upper_bound * 2.0 / (1.0 + self.gamma_ln.exp())
+    }
+}
+
+impl Default for QuantileSketch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Sketches the values of a log over time without keeping them. Each value is counted in a
+/// [`QuantileSketch`] for the slot of time that its timestamp falls into. Slots start out a
+/// nanosecond long and double in length whenever there are more than `MAX_SLOTS` of them, so
+/// however long the log, a column of a sparkline spans many slots.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SketchTimeline {
+    /// Length of each slot in nanoseconds
+    slot_size: i64,
+    /// The slots that have values, keyed by their start divided by `slot_size`
+    slots: BTreeMap<i64, Slot>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Slot {
+    first_timestamp: i64,
+    last_timestamp: i64,
+    sketch: QuantileSketch,
+}
+
+impl Slot {
+    fn merge(&mut self, other: &Slot) {
+        self.first_timestamp = self.first_timestamp.min(other.first_timestamp);
+        self.last_timestamp = self.last_timestamp.max(other.last_timestamp);
+        self.sketch.merge(&other.sketch);
+    }
+}
+
+impl SketchTimeline {
+    pub fn new() -> SketchTimeline {
+        SketchTimeline {
+            slot_size: 1,
+            slots: BTreeMap::new(),
+        }
+    }
+
+    /// Counts a value with the timestamp of its line, in nanoseconds since the UNIX epoch.
+    pub fn insert(&mut self, timestamp: i64, value: f64) {
+        let slot = self
+            .slots
+            .entry(timestamp.div_euclid(self.slot_size))
+            .or_insert_with(|| Slot {
+                first_timestamp: timestamp,
+                last_timestamp: timestamp,
+                sketch: QuantileSketch::new(),
+            });
+        slot.first_timestamp = slot.first_timestamp.min(timestamp);
+        slot.last_timestamp = slot.last_timestamp.max(timestamp);
+        slot.sketch.insert(value);
+        self.shrink();
+    }
+
+    /// Combines the timelines of two parts of a log, or of two logs.
+    pub fn merge(mut self, mut other: SketchTimeline) -> SketchTimeline {
+        while self.slot_size < other.slot_size {
+            self.coarsen();
+        }
+        while other.slot_size < self.slot_size {
+            other.coarsen();
+        }
+        for (key, slot) in other.slots {
+            self.add(key, slot);
+        }
+        self.shrink();
+        self
+    }
+
+    /// Forgets the slots whose values all have timestamps before `start`.
+    pub fn retain_since(&mut self, start: i64) {
+        self.slots.retain(|_, slot| slot.last_timestamp >= start);
+    }
+
+    /// Whether no values were counted.
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+
+    /// Puts the slots together into `bucket_count` sketches. A slot goes into the bucket that
+    /// `bucket_of` finds for its first timestamp, or else for its last one, so the values of a slot
+    /// that straddles two buckets are all counted in one of them.
+    pub(crate) fn buckets(
+        &self,
+        bucket_count: usize,
+        bucket_of: impl Fn(i64) -> Option<usize>,
+    ) -> Vec<QuantileSketch> {
+        let mut sketches = vec![QuantileSketch::new(); bucket_count];
+        for slot in self.slots.values() {
+            let bucket_index =
+                bucket_of(slot.first_timestamp).or_else(|| bucket_of(slot.last_timestamp));
+            if let Some(bucket_index) = bucket_index {
+                sketches[bucket_index].merge(&slot.sketch);
+            }
+        }
+        sketches
+    }
+
+    fn add(&mut self, key: i64, slot: Slot) {
+        match self.slots.entry(key) {
+            Entry::Vacant(entry) => {
+                entry.insert(slot);
+            }
+            Entry::Occupied(mut entry) => entry.get_mut().merge(&slot),
+        }
+    }
+
+    fn shrink(&mut self) {
+        while self.slots.len() > MAX_SLOTS {
+            self.coarsen();
+        }
+    }
+
+    /// Doubles the length of the slots, merging each pair of neighbours.
+    fn coarsen(&mut self) {
+        self.slot_size *= 2;
+        for (key, slot) in std::mem::take(&mut self.slots) {
+            self.add(key.div_euclid(2), slot);
+        }
+    }
+}
+
+impl Default for SketchTimeline {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Aggregate;
+
+    #[test]
+    fn quantile_sketch_percentile() {
+        let mut values: Vec<f64> = (1..=10_000)
+            .map(|i| (i as f64 * 7.3) % 5_000.0 - 100.0)
+            .collect();
+        values.extend([0.0; 50]);
+        let mut sketch = QuantileSketch::new();
+        values.iter().for_each(|value| sketch.insert(*value));
+        sketch.insert(f64::NAN);
+        assert_eq!(sketch.count(), 10_050);
+
+        for percentile in [1.0, 10.0, 50.0, 90.0, 99.0, 99.9] {
+            let exact = Aggregate::Percentile(percentile)
+                .apply(&mut values)
+                .unwrap();
+            let estimate = sketch.percentile(percentile).unwrap();
+            assert!(
+                (estimate - exact).abs() <= exact.abs() * RELATIVE_ACCURACY,
+                "p{}: estimated {} for {}",
+                percentile,
+                estimate,
+                exact
+            );
+        }
+        let max = values.iter().copied().reduce(f64::max);
+        assert_eq!(sketch.percentile(100.0), max);
+    }
+
+    #[test]
+    fn quantile_sketch_merge() {
+        let mut all = QuantileSketch::new();
+        let mut low = QuantileSketch::new();
+        let mut high = QuantileSketch::new();
+        for i in 1..=1_000 {
+            let value = i as f64 - 200.0;
+            all.insert(value);
+            if i % 3 == 0 {
+                low.insert(value);
+            } else {
+                high.insert(value);
+            }
+        }
+        low.merge(&high);
+        assert_eq!(low, all);
+        low.merge(&QuantileSketch::new());
+        assert_eq!(low, all);
+    }
+
+    #[test]
+    fn quantile_sketch_small() {
+        let mut sketch = QuantileSketch::new();
+        assert_eq!(sketch.percentile(50.0), None);
+
+        sketch.insert(78.0);
+        assert_eq!(sketch.percentile(1.0), Some(78.0));
+        assert_eq!(sketch.percentile(99.0), Some(78.0));
+
+        sketch.insert(0.0);
+        sketch.insert(-5.0);
+        assert_eq!(sketch.percentile(1.0), Some(-5.0));
+        assert_eq!(sketch.percentile(50.0), Some(0.0));
+    }
+
+    #[test]
+    fn sketch_timeline_buckets() {
+        const MINUTE: i64 = 60_000_000_000;
+        let mut timeline = SketchTimeline::new();
+        for i in 0..100 {
+            timeline.insert(MINUTE + i, i as f64);
+            timeline.insert(2 * MINUTE + i, 1_000.0);
+        }
+        let bucket_of = |timestamp: i64| match timestamp / MINUTE {
+            1 => Some(0),
+            2 => Some(1),
+            _ => None,
+        };
+        let buckets = timeline.buckets(3, bucket_of);
+        assert_eq!(buckets[0].count(), 100);
+        assert_eq!(buckets[0].percentile(100.0), Some(99.0));
+        assert_eq!(buckets[1].percentile(50.0), Some(1_000.0));
+        assert_eq!(buckets[2].count(), 0);
+
+        timeline.retain_since(2 * MINUTE);
+        let buckets = timeline.buckets(3, bucket_of);
+        assert_eq!(buckets[0].count(), 0);
+        assert_eq!(buckets[1].count(), 100);
+    }
+
+    #[test]
+    fn sketch_timeline_merge() {
+        const SECOND: i64 = 1_000_000_000;
+        let value = |i: i64| (i % 97) as f64;
+        let mut all = SketchTimeline::new();
+        let mut first = SketchTimeline::new();
+        let mut second = SketchTimeline::new();
+        for i in 0..20_000 {
+            all.insert(i * SECOND, value(i));
+            if i < 15_000 {
+                first.insert(i * SECOND, value(i));
+            } else {
+                second.insert(i * SECOND, value(i));
+            }
+        }
+        // The slots grow instead of there being one for each value
+        assert!(all.slots.len() <= MAX_SLOTS);
+        assert!(second.slot_size < first.slot_size);
+        assert_eq!(second.merge(first), all);
+        assert_eq!(all.clone().merge(SketchTimeline::new()), all);
+    }
+}
@@ -0,0 +1,148 @@
+use crate::timestamp_finder::ScannedLine;
+
+/// Number of lines without a timestamp that are kept as examples.
+pub const UNMATCHED_SAMPLE_LINES: usize = 5;
+
+/// How well a scan went, for checking that the timestamp format covers the log.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ScanStats {
+    pub total_lines: usize,
+    /// Number of lines with a timestamp.
+    pub matched_lines: usize,
+    /// Number of lines with a timestamp outside of the finder's time range, which aren't counted
+    /// as matched.
+    pub out_of_range_lines: usize,
+    /// Number of lines that the finder's line filter left out.
+    pub filtered_lines: usize,
+    /// Number of lines where something looked like a timestamp in the format but wasn't a valid
+    /// date or time.
+    pub unparseable_lines: usize,
+    /// Number of lines that the extractor couldn't parse, e.g. lines that aren't valid JSON.
+    pub malformed_lines: usize,
+    /// Number of lines that aren't valid UTF-8. They're still scanned, with the invalid bytes
+    /// replaced by U+FFFD.
+    pub invalid_utf8_lines: usize,
+    /// Number of lines with a timestamp where the finder's value regex found no number.
+    pub missing_value_lines: usize,
+    /// The earliest timestamp, in nanoseconds since the UNIX epoch.
+    pub first_timestamp: Option<i64>,
+    /// The latest timestamp, in nanoseconds since the UNIX epoch.
+    pub last_timestamp: Option<i64>,
+    /// The first few lines without a timestamp.
+    pub unmatched_samples: Vec<String>,
+}
+
+impl ScanStats {
+    /// Time between the first and the last timestamp, in nanoseconds.
+    pub fn time_span(&self) -> Option<i64> {
+        Some(self.last_timestamp? - self.first_timestamp?)
+    }
+
+    pub(crate) fn record(&mut self, line: &str, scanned: &ScannedLine) {
+        self.total_lines += 1;
+        match scanned {
+            ScannedLine::Timestamp(timestamp) => {
+                self.matched_lines += 1;
+                self.first_timestamp = Some(
+                    self.first_timestamp
+                        .map_or(*timestamp, |first| first.min(*timestamp)),
+                );
+                self.last_timestamp = Some(
+                    self.last_timestamp
+                        .map_or(*timestamp, |last| last.max(*timestamp)),
+                );
+                return;
+            }
+            ScannedLine::OutOfRange(_) => {
+                self.out_of_range_lines += 1;
+                return;
+            }
+            ScannedLine::Filtered => {
+                self.filtered_lines += 1;
+                return;
+            }
+            ScannedLine::NoTimestamp => {}
+            ScannedLine::Unparseable => self.unparseable_lines += 1,
+            ScannedLine::Malformed => self.malformed_lines += 1,
+        }
+        if self.unmatched_samples.len() < UNMATCHED_SAMPLE_LINES {
+            self.unmatched_samples.push(line.to_string());
+        }
+    }
+
+    /// Counts what was found on a line with a timestamp when reading numbers from lines.
+    pub(crate) fn record_value(&mut self, value: Option<f64>) {
+        if value.is_none() {
+            self.missing_value_lines += 1;
+        }
+    }
+
+    /// Combines the statistics of consecutive parts of a log.
+    pub fn merge(mut self, other: ScanStats) -> ScanStats {
+        self.total_lines += other.total_lines;
+        self.matched_lines += other.matched_lines;
+        self.out_of_range_lines += other.out_of_range_lines;
+        self.filtered_lines += other.filtered_lines;
+        self.unparseable_lines += other.unparseable_lines;
+        self.malformed_lines += other.malformed_lines;
+        self.invalid_utf8_lines += other.invalid_utf8_lines;
+        self.missing_value_lines += other.missing_value_lines;
+        self.first_timestamp = combine(self.first_timestamp, other.first_timestamp, i64::min);
+        self.last_timestamp = combine(self.last_timestamp, other.last_timestamp, i64::max);
+        let room = UNMATCHED_SAMPLE_LINES.saturating_sub(self.unmatched_samples.len());
+        self.unmatched_samples
+            .extend(other.unmatched_samples.into_iter().take(room));
+        self
+    }
+}
+
+fn combine(a: Option<i64>, b: Option<i64>, pick: fn(i64, i64) -> i64) -> Option<i64> {
+    match (a, b) {
+        (Some(a), Some(b)) => Some(pick(a, b)),
+        (a, b) => a.or(b),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scan_stats_record_and_merge() {
+        let mut first = ScanStats::default();
+        first.record("a", &ScannedLine::Timestamp(20));
+        first.record("b", &ScannedLine::NoTimestamp);
+        first.record("c", &ScannedLine::Unparseable);
+        first.record("x", &ScannedLine::OutOfRange(5));
+        first.record("y", &ScannedLine::Filtered);
+        let mut second = ScanStats::default();
+        second.record("d", &ScannedLine::Timestamp(10));
+        second.record("e", &ScannedLine::Timestamp(30));
+        for line in ["f", "g", "h", "i"] {
+            second.record(line, &ScannedLine::Malformed);
+        }
+
+        let stats = first.merge(second);
+        assert_eq!(
+            stats,
+            ScanStats {
+                total_lines: 11,
+                matched_lines: 3,
+                out_of_range_lines: 1,
+                filtered_lines: 1,
+                unparseable_lines: 1,
+                malformed_lines: 4,
+                invalid_utf8_lines: 0,
+                missing_value_lines: 0,
+                first_timestamp: Some(10),
+                last_timestamp: Some(30),
+                unmatched_samples: vec!["b", "c", "f", "g", "h"]
+                    .into_iter()
+                    .map(String::from)
+                    .collect(),
+            }
+        );
+        assert_eq!(stats.time_span(), Some(20));
+        assert_eq!(ScanStats::default().time_span(), None);
+    }
+}
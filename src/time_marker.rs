@@ -1,4 +1,5 @@
-use anyhow::{anyhow, Result};
+use crate::timezone::Timezone;
+use anyhow::Result;
 use chrono::DateTime;
 use std::fmt;
 
@@ -7,6 +8,47 @@ pub enum TimestampLocation {
     Bottom,
 }
 
+/// How precisely the timestamp of a marker is displayed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Precision {
+    Seconds,
+    Milliseconds,
+    Microseconds,
+    Nanoseconds,
+}
+
+impl Precision {
+    /// Picks the coarsest precision that still tells apart timestamps that are `spacing`
+    /// nanoseconds apart.
+    pub fn for_spacing(spacing: i64) -> Precision {
+        match spacing {
+            1_000_000_000.. => Precision::Seconds,
+            1_000_000.. => Precision::Milliseconds,
+            1_000.. => Precision::Microseconds,
+            _ => Precision::Nanoseconds,
+        }
+    }
+
+    /// Number of characters that a timestamp takes up at this precision.
+    pub fn width(&self) -> usize {
+        match self {
+            Precision::Seconds => 19,
+            Precision::Milliseconds => 23,
+            Precision::Microseconds => 26,
+            Precision::Nanoseconds => 29,
+        }
+    }
+
+    fn format(&self) -> &'static str {
+        match self {
+            Precision::Seconds => "%Y-%m-%d %H:%M:%S",
+            Precision::Milliseconds => "%Y-%m-%d %H:%M:%S%.3f",
+            Precision::Microseconds => "%Y-%m-%d %H:%M:%S%.6f",
+            Precision::Nanoseconds => "%Y-%m-%d %H:%M:%S%.9f",
+        }
+    }
+}
+
 pub struct Canvas {
     buffer: Vec<String>,
     height: usize,
@@ -38,16 +80,20 @@ impl fmt::Display for Canvas {
 pub struct TimeMarker {
     pub horizontal_offset: usize,
     pub timestamp_location: TimestampLocation,
+    /// Nanoseconds since the UNIX epoch
     pub timestamp: i64,
     pub vertical_offset: usize,
+    pub precision: Precision,
+    /// The timezone whose wall clock the timestamp is shown in
+    pub timezone: Timezone,
 }
 
 impl TimeMarker {
     pub fn render(&self, canvas: &mut Canvas) -> Result<()> {
-        let time = DateTime::from_timestamp(self.timestamp, 0)
-            .ok_or(anyhow!("timestamp is invalid: {}", self.timestamp))?
-            .to_string()
-            .replace(" UTC", "");
+        let wall_clock = self.timestamp + self.timezone.utc_offset(self.timestamp);
+        let time = DateTime::from_timestamp_nanos(wall_clock)
+            .format(self.precision.format())
+            .to_string();
 
         let (stem_rows, timestamp_row, timestamp_horizontal_offset) = match &self.timestamp_location
         {
@@ -96,6 +142,8 @@ mod tests {
             timestamp_location: TimestampLocation::Top,
             timestamp: 0,
             vertical_offset: 1,
+            precision: Precision::Seconds,
+            timezone: Timezone::Utc,
         };
 
         time_marker.render(&mut canvas).expect("failed to render");
@@ -121,6 +169,8 @@ mod tests {
             timestamp_location: TimestampLocation::Bottom,
             timestamp: 0,
             vertical_offset: 1,
+            precision: Precision::Seconds,
+            timezone: Timezone::Utc,
         };
 
         time_marker.render(&mut canvas).expect("failed to render");
@@ -145,18 +195,24 @@ mod tests {
             timestamp_location: TimestampLocation::Top,
             timestamp: 0,
             vertical_offset: 1,
+            precision: Precision::Seconds,
+            timezone: Timezone::Utc,
         };
         let time_marker2 = TimeMarker {
             horizontal_offset: 40,
             timestamp_location: TimestampLocation::Top,
-            timestamp: 1000,
+            timestamp: 1_000_000_000_000,
             vertical_offset: 2,
+            precision: Precision::Seconds,
+            timezone: Timezone::Utc,
         };
         let time_marker3 = TimeMarker {
             horizontal_offset: 60,
             timestamp_location: TimestampLocation::Top,
-            timestamp: 2000,
+            timestamp: 2_000_000_000_000,
             vertical_offset: 3,
+            precision: Precision::Seconds,
+            timezone: Timezone::Utc,
         };
 
         [time_marker, time_marker2, time_marker3]
@@ -186,18 +242,24 @@ mod tests {
             timestamp_location: TimestampLocation::Bottom,
             timestamp: 0,
             vertical_offset: 3,
+            precision: Precision::Seconds,
+            timezone: Timezone::Utc,
         };
         let time_marker2 = TimeMarker {
             horizontal_offset: 40,
             timestamp_location: TimestampLocation::Bottom,
-            timestamp: 1000,
+            timestamp: 1_000_000_000_000,
             vertical_offset: 2,
+            precision: Precision::Seconds,
+            timezone: Timezone::Utc,
         };
         let time_marker3 = TimeMarker {
             horizontal_offset: 60,
             timestamp_location: TimestampLocation::Bottom,
-            timestamp: 2000,
+            timestamp: 2_000_000_000_000,
             vertical_offset: 1,
+            precision: Precision::Seconds,
+            timezone: Timezone::Utc,
         };
 
         [time_marker, time_marker2, time_marker3]
@@ -218,4 +280,59 @@ mod tests {
 "
         );
     }
+
+    #[test]
+    fn timemarker_render_milliseconds() {
+        let mut canvas = Canvas::new(30, 2);
+        let time_marker = TimeMarker {
+            horizontal_offset: 0,
+            timestamp_location: TimestampLocation::Bottom,
+            timestamp: 1_574_490_400_781_000_000,
+            vertical_offset: 1,
+            precision: Precision::Milliseconds,
+            timezone: Timezone::Utc,
+        };
+
+        time_marker.render(&mut canvas).expect("failed to render");
+        let rendered = format!("\n{}", canvas);
+        assert_eq!(
+            rendered,
+            "
+|                             
+2019-11-23 06:26:40.781       
+"
+        );
+    }
+
+    #[test]
+    fn timemarker_render_timezone() {
+        let mut canvas = Canvas::new(25, 2);
+        let time_marker = TimeMarker {
+            horizontal_offset: 0,
+            timestamp_location: TimestampLocation::Bottom,
+            timestamp: 1_704_164_645_000_000_000,
+            vertical_offset: 1,
+            precision: Precision::Seconds,
+            timezone: "Europe/Berlin".parse().unwrap(),
+        };
+
+        time_marker.render(&mut canvas).expect("failed to render");
+        let rendered = format!("\n{}", canvas);
+        assert_eq!(
+            rendered,
+            "
+|                        
+2024-01-02 04:04:05      
+"
+        );
+    }
+
+    #[test]
+    fn precision_for_spacing() {
+        assert_eq!(Precision::for_spacing(60_000_000_000), Precision::Seconds);
+        assert_eq!(Precision::for_spacing(1_000_000_000), Precision::Seconds);
+        assert_eq!(Precision::for_spacing(999_999_999), Precision::Milliseconds);
+        assert_eq!(Precision::for_spacing(5_000), Precision::Microseconds);
+        assert_eq!(Precision::for_spacing(10), Precision::Nanoseconds);
+    }
 }
@@ -0,0 +1,160 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Utc};
+use std::str::FromStr;
+
+use crate::time_span::TimeSpan;
+use crate::timezone::Timezone;
+
+/// Formats of the dates and times that `TimeBound` accepts without a UTC offset.
+const DATETIME_FORMATS: &[&str] = &[
+    "%Y-%m-%d %H:%M:%S%.f",
+    "%Y-%m-%dT%H:%M:%S%.f",
+    "%Y-%m-%d %H:%M",
+    "%Y-%m-%dT%H:%M",
+];
+
+/// One end of a time range as it's given on the command line: a date and time, like
+/// "2024-01-02 03:04:05", or a time relative to now, like "2h ago".
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum TimeBound {
+    Now,
+    Ago(TimeSpan),
+    /// A date and time with a UTC offset, as in RFC 3339.
+    Absolute(DateTime<FixedOffset>),
+    /// A date and time on the wall clock, to be read in the timezone of the log.
+    Local(NaiveDateTime),
+}
+
+impl TimeBound {
+    /// Resolves the bound to nanoseconds since the UNIX epoch. Relative bounds count back from
+    /// `now`, and dates and times without a UTC offset are in `timezone`.
+    pub fn resolve(&self, now: DateTime<Utc>, timezone: &Timezone) -> Option<i64> {
+        match self {
+            TimeBound::Now => now.timestamp_nanos_opt(),
+            TimeBound::Ago(duration) => Some(now.timestamp_nanos_opt()? - duration.as_nanos()),
+            TimeBound::Absolute(datetime) => datetime.timestamp_nanos_opt(),
+            TimeBound::Local(datetime) => timezone.to_utc(datetime).timestamp_nanos_opt(),
+        }
+    }
+}
+
+impl FromStr for TimeBound {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        if s == "now" {
+            return Ok(TimeBound::Now);
+        }
+        if let Some(duration) = s.strip_suffix("ago") {
+            return Ok(TimeBound::Ago(duration.parse()?));
+        }
+        if let Ok(datetime) = DateTime::parse_from_rfc3339(s) {
+            return Ok(TimeBound::Absolute(datetime));
+        }
+        for format in DATETIME_FORMATS {
+            if let Ok(datetime) = NaiveDateTime::parse_from_str(s, format) {
+                return Ok(TimeBound::Local(datetime));
+            }
+        }
+        if let Ok(date) = NaiveDate::parse_from_str(s, "%Y-%m-%d") {
+            return Ok(TimeBound::Local(date.and_time(Default::default())));
+        }
+        Err(anyhow!(
+            "invalid time '{}' (expected a date and time like 2024-01-02 03:04:05, or a time relative to now like 2h ago)",
+            s
+        ))
+    }
+}
+
+/// The span of time that timestamps are kept from. Either end may be open, and both are
+/// inclusive.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct TimeRange {
+    /// The earliest timestamp to keep, in nanoseconds since the UNIX epoch.
+    pub since: Option<i64>,
+    /// The latest timestamp to keep, in nanoseconds since the UNIX epoch.
+    pub until: Option<i64>,
+}
+
+impl TimeRange {
+    pub fn contains(&self, timestamp: i64) -> bool {
+        self.since.is_none_or(|since| timestamp >= since)
+            && self.until.is_none_or(|until| timestamp <= until)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_bound_from_str() {
+        assert_eq!("now".parse::<TimeBound>().unwrap(), TimeBound::Now);
+        assert_eq!(
+            "2h ago".parse::<TimeBound>().unwrap(),
+            TimeBound::Ago("2h".parse().unwrap())
+        );
+        assert_eq!(
+            "1h30m ago".parse::<TimeBound>().unwrap(),
+            TimeBound::Ago("90m".parse().unwrap())
+        );
+        assert!(matches!(
+            "2024-01-02T03:04:05+02:00".parse::<TimeBound>().unwrap(),
+            TimeBound::Absolute(_)
+        ));
+        let local = |s: &str| match s.parse::<TimeBound>().unwrap() {
+            TimeBound::Local(datetime) => datetime.to_string(),
+            bound => panic!("{:?} isn't a local time", bound),
+        };
+        assert_eq!(local("2024-01-02 03:04:05"), "2024-01-02 03:04:05");
+        assert_eq!(local("2024-01-02T03:04"), "2024-01-02 03:04:00");
+        assert_eq!(local("2024-01-02"), "2024-01-02 00:00:00");
+        assert!("yesterday".parse::<TimeBound>().is_err());
+        assert!("2h".parse::<TimeBound>().is_err());
+        assert!("soon ago".parse::<TimeBound>().is_err());
+    }
+
+    #[test]
+    fn time_bound_resolve() {
+        let now = DateTime::from_timestamp(1_704_164_645, 0).unwrap();
+        let resolve = |s: &str, timezone: &str| {
+            s.parse::<TimeBound>()
+                .unwrap()
+                .resolve(now, &timezone.parse().unwrap())
+                .unwrap()
+        };
+        assert_eq!(resolve("now", "UTC"), 1_704_164_645_000_000_000);
+        assert_eq!(resolve("2h ago", "UTC"), 1_704_157_445_000_000_000);
+        assert_eq!(
+            resolve("2024-01-02 03:04:05", "UTC"),
+            1_704_164_645_000_000_000
+        );
+        assert_eq!(
+            resolve("2024-01-02 05:04:05", "+02:00"),
+            1_704_164_645_000_000_000
+        );
+        assert_eq!(
+            resolve("2024-01-02T05:04:05+02:00", "-07:00"),
+            1_704_164_645_000_000_000
+        );
+    }
+
+    #[test]
+    fn time_range_contains() {
+        let range = TimeRange {
+            since: Some(10),
+            until: Some(20),
+        };
+        assert!(!range.contains(9));
+        assert!(range.contains(10));
+        assert!(range.contains(20));
+        assert!(!range.contains(21));
+        assert!(TimeRange::default().contains(i64::MIN));
+        let since = TimeRange {
+            since: Some(10),
+            until: None,
+        };
+        assert!(since.contains(i64::MAX));
+    }
+}
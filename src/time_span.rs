@@ -0,0 +1,120 @@
+use anyhow::{anyhow, Result};
+use std::fmt;
+use std::str::FromStr;
+
+/// A length of time given on the command line, like "15m" or "1h30m".
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimeSpan {
+    nanoseconds: i64,
+}
+
+const UNITS: &[(&str, i64)] = &[
+    ("ms", 1_000_000),
+    ("s", 1_000_000_000),
+    ("m", 60 * 1_000_000_000),
+    ("h", 3_600 * 1_000_000_000),
+    ("d", 86_400 * 1_000_000_000),
+    ("w", 7 * 86_400 * 1_000_000_000),
+];
+
+impl TimeSpan {
+    pub fn as_nanos(self) -> i64 {
+        self.nanoseconds
+    }
+
+    pub fn as_std(self) -> std::time::Duration {
+        let nanoseconds = u64::try_from(self.nanoseconds).expect("time spans are positive");
+        std::time::Duration::from_nanos(nanoseconds)
+    }
+}
+
+impl FromStr for TimeSpan {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || {
+            anyhow!(
+                "invalid duration '{}' (expected a number and a unit, like 90s, 15m, 1h30m or 2d)",
+                s
+            )
+        };
+
+        let mut nanoseconds: i64 = 0;
+        let mut rest = s.trim();
+        if rest.is_empty() {
+            return Err(invalid());
+        }
+        while !rest.is_empty() {
+            let digits = rest
+                .find(|c: char| !c.is_ascii_digit())
+                .ok_or_else(invalid)?;
+            let value: i64 = rest[..digits].parse().map_err(|_| invalid())?;
+            rest = &rest[digits..];
+            let unit_len = rest
+                .find(|c: char| c.is_ascii_digit())
+                .unwrap_or(rest.len());
+            let &(_, unit) = UNITS
+                .iter()
+                .find(|(name, _)| *name == &rest[..unit_len])
+                .ok_or_else(invalid)?;
+            rest = &rest[unit_len..];
+            nanoseconds = value
+                .checked_mul(unit)
+                .and_then(|value| nanoseconds.checked_add(value))
+                .ok_or_else(invalid)?;
+        }
+        match nanoseconds {
+            0 => Err(anyhow!("duration '{}' must be longer than zero", s)),
+            _ => Ok(TimeSpan { nanoseconds }),
+        }
+    }
+}
+
+impl fmt::Display for TimeSpan {
+    /// Formats the duration with the fewest units, like "1h30m".
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut rest = self.nanoseconds;
+        for &(name, unit) in UNITS.iter().rev() {
+            if rest >= unit {
+                write!(f, "{}{}", rest / unit, name)?;
+                rest %= unit;
+            }
+        }
+        if rest > 0 {
+            write!(f, "{}ns", rest)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn time_span_from_str() {
+        let parse = |s: &str| s.parse::<TimeSpan>().map(TimeSpan::as_nanos).ok();
+        assert_eq!(parse("90s"), Some(90_000_000_000));
+        assert_eq!(parse("15m"), Some(900_000_000_000));
+        assert_eq!(parse("1h30m"), Some(5_400_000_000_000));
+        assert_eq!(parse("2d"), Some(172_800_000_000_000));
+        assert_eq!(parse("1w"), Some(604_800_000_000_000));
+        assert_eq!(parse("250ms"), Some(250_000_000));
+        assert_eq!(parse(""), None);
+        assert_eq!(parse("15"), None);
+        assert_eq!(parse("m"), None);
+        assert_eq!(parse("15x"), None);
+        assert_eq!(parse("0s"), None);
+        assert_eq!(parse("-5m"), None);
+        assert_eq!(parse("99999999999w"), None);
+    }
+
+    #[test]
+    fn time_span_to_string() {
+        let format = |s: &str| s.parse::<TimeSpan>().unwrap().to_string();
+        assert_eq!(format("15m"), "15m");
+        assert_eq!(format("90m"), "1h30m");
+        assert_eq!(format("86400s"), "1d");
+        assert_eq!(format("1500ms"), "1s500ms");
+    }
+}
@@ -1,45 +1,714 @@
-use chrono::NaiveDateTime;
-use regex::Regex;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, TimeDelta, Utc};
+use regex::{Captures, Match, Regex};
+use std::borrow::Cow;
+use std::str::FromStr;
+
+use crate::extractor::{Extractor, Field};
+use crate::line_filter::LineFilter;
+use crate::presets::find_preset;
+use crate::time_range::TimeRange;
+use crate::timezone::Timezone;
 
 pub struct TimestampFinder {
+    formats: Vec<Format>,
+    timezone: Timezone,
+    reference_date: NaiveDate,
+    extractor: Extractor,
+    occurrence: Occurrence,
+    anchored: bool,
+    range: TimeRange,
+    filter: LineFilter,
+    group_by: Option<Regex>,
+    value: Option<Regex>,
+    sketch_values: bool,
+}
+
+/// Which timestamp to use on lines that contain several, like proxy logs with both the time that
+/// a request started and the time that its response ended.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Occurrence {
+    #[default]
+    First,
+    Last,
+    /// The nth timestamp on the line, counting from 1.
+    Nth(usize),
+}
+
+impl FromStr for Occurrence {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "first" => Ok(Occurrence::First),
+            "last" => Ok(Occurrence::Last),
+            _ => match s.parse::<usize>() {
+                Ok(n) if n > 0 => Ok(Occurrence::Nth(n)),
+                _ => Err(anyhow!(
+                    "invalid occurrence '{}' (expected 'first', 'last' or a number starting at 1)",
+                    s
+                )),
+            },
+        }
+    }
+}
+
+/// A timestamp format, compiled to the regex that finds it.
+struct Format {
     datetime_format: String,
     regex: Regex,
+    offset_source: OffsetSource,
+    missing_year: bool,
+    epoch_unit: Option<EpochUnit>,
+    /// Whether the timestamp is a bare number, like a UNIX timestamp, which mustn't be read from
+    /// part of a longer number such as an IP address.
+    numeric: bool,
+}
+
+/// What `TimestampFinder::scan_line` found in a line.
+#[derive(Debug, PartialEq)]
+pub enum ScannedLine {
+    /// A timestamp in nanoseconds since the UNIX epoch.
+    Timestamp(i64),
+    NoTimestamp,
+    /// Something on the line looks like a timestamp in the format, but it isn't a valid date or
+    /// time, like "31/Feb/2024:25:00:00".
+    Unparseable,
+    /// The line couldn't be parsed by the finder's extractor, e.g. because it isn't valid JSON.
+    Malformed,
+    /// A timestamp outside of the finder's time range.
+    OutOfRange(i64),
+    /// The finder's line filter left the line out, so it wasn't searched.
+    Filtered,
+}
+
+/// Whether `number` isn't part of a longer number in `s`, i.e. it isn't next to another digit or to
+/// a decimal point that's followed by one, as in "10.1.1.10".
+fn is_whole_number(s: &str, number: Match) -> bool {
+    let before = &s.as_bytes()[..number.start()];
+    let after = &s.as_bytes()[number.end()..];
+    let continues_before = match before {
+        [.., digit] if digit.is_ascii_digit() => true,
+        [.., digit, b'.'] => digit.is_ascii_digit(),
+        _ => false,
+    };
+    let continues_after = match after {
+        [digit, ..] if digit.is_ascii_digit() => true,
+        [b'.', digit, ..] => digit.is_ascii_digit(),
+        _ => false,
+    };
+    !continues_before && !continues_after
+}
+
+/// Units of UNIX timestamps. Seconds are written `%s`, and the units that strftime can't express
+/// each have a named format, like "epoch_ms".
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum EpochUnit {
+    Seconds,
+    Milliseconds,
+    Microseconds,
+    Nanoseconds,
+    /// Seconds with a fractional part, like "1621568291.123"
+    FractionalSeconds,
+}
+
+impl EpochUnit {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "epoch_ms" => Some(EpochUnit::Milliseconds),
+            "epoch_us" => Some(EpochUnit::Microseconds),
+            "epoch_ns" => Some(EpochUnit::Nanoseconds),
+            "epoch_frac" => Some(EpochUnit::FractionalSeconds),
+            _ => None,
+        }
+    }
+
+    /// Guesses the unit of a UNIX timestamp from its number of digits, assuming that it's from
+    /// the last few decades.
+    fn for_digits(digits: usize) -> Self {
+        match digits {
+            0..=11 => EpochUnit::Seconds,
+            12..=14 => EpochUnit::Milliseconds,
+            15..=17 => EpochUnit::Microseconds,
+            _ => EpochUnit::Nanoseconds,
+        }
+    }
+
+    /// Matches the digits of a timestamp in this unit.
+    fn regex(&self) -> &'static str {
+        match self {
+            EpochUnit::Seconds => r"\d{1,10}",
+            EpochUnit::Milliseconds => r"\d{13}",
+            EpochUnit::Microseconds => r"\d{16}",
+            EpochUnit::Nanoseconds => r"\d{19}",
+            EpochUnit::FractionalSeconds => r"\d{10}\.\d{1,9}",
+        }
+    }
+
+    /// Converts a matched timestamp to nanoseconds since the UNIX epoch.
+    fn to_nanos(self, s: &str) -> Option<i64> {
+        match self {
+            EpochUnit::Seconds => s.parse::<i64>().ok()?.checked_mul(1_000_000_000),
+            EpochUnit::Milliseconds => s.parse::<i64>().ok()?.checked_mul(1_000_000),
+            EpochUnit::Microseconds => s.parse::<i64>().ok()?.checked_mul(1_000),
+            EpochUnit::Nanoseconds => s.parse::<i64>().ok(),
+            EpochUnit::FractionalSeconds => {
+                // The sign applies to the fraction too, so -1.5 is a second and a half before 1970
+                let (sign, magnitude) = match s.strip_prefix('-') {
+                    Some(magnitude) => (-1, magnitude),
+                    None => (1, s),
+                };
+                let (seconds, fraction) = magnitude.split_once('.')?;
+                let nanos: i64 = format!("{:0<9}", fraction).parse().ok()?;
+                seconds
+                    .parse::<i64>()
+                    .ok()?
+                    .checked_mul(1_000_000_000)?
+                    .checked_add(nanos)?
+                    .checked_mul(sign)
+            }
+        }
+    }
+}
+
+/// Where the UTC offset of a matched timestamp comes from.
+enum OffsetSource {
+    /// The timestamp carries a numeric offset, or it's a UNIX timestamp and therefore always UTC.
+    Embedded,
+    /// The timestamp contains a timezone name (`%Z`). Chrono can't resolve names, so the name is
+    /// cut out of the match and the remainder is parsed with `parse_format`.
+    Name { parse_format: String },
+    /// The timestamp has no offset and is interpreted in the finder's timezone.
+    Assumed,
+}
+
+/// Specifiers that provide the year, either on their own or as part of a composite specifier.
+const YEAR_SPECIFIERS: &[&str] = &["Y", "y", "G", "g", "s", "+", "F", "D", "x", "v", "c"];
+
+/// Specifiers that are longer than a single character, ordered so that longer ones are tried first.
+const MULTI_CHAR_SPECIFIERS: &[&str] = &[
+    ":::z", "::z", ":z", "#z", ".3f", ".6f", ".9f", ".f", "3f", "6f", "9f",
+];
+
+/// Name of the capture group that holds the timezone name matched by `%Z`.
+const TIMEZONE_NAME_GROUP: &str = "tz";
+
+/// A piece of a strftime-style format string.
+#[derive(Debug, PartialEq)]
+enum FormatToken<'a> {
+    /// Text that must appear verbatim.
+    Literal(&'a str),
+    /// A run of whitespace. Chrono accepts any amount of whitespace here when parsing.
+    Whitespace,
+    /// A conversion specifier such as `%Y` or `%:z`, including its leading `%` and any
+    /// padding modifier.
+    Specifier(&'a str),
 }
 
 impl TimestampFinder {
-    pub fn new(datetime_format: &str) -> Result<Self, anyhow::Error> {
-        let datetime_regex = Self::strftime_to_regex(datetime_format);
-        let regex = Regex::new(&datetime_regex)?;
+    /// Creates a finder for `datetime_format`, which is either a strftime-style format, the name
+    /// of a preset like "clf", or one of the UNIX timestamp formats "epoch_ms", "epoch_us",
+    /// "epoch_ns" and "epoch_frac".
+    pub fn new(datetime_format: &str) -> Result<Self> {
+        Self::from_formats(&[datetime_format])
+    }
+
+    /// Creates a finder for logs that mix several timestamp formats. The formats are tried in
+    /// order on each line, and the first one that matches wins.
+    pub fn from_formats<S: AsRef<str>>(datetime_formats: &[S]) -> Result<Self> {
+        if datetime_formats.is_empty() {
+            return Err(anyhow!("no timestamp format given"));
+        }
+        let formats = datetime_formats
+            .iter()
+            .map(|format| Self::compile(format.as_ref()))
+            .collect::<Result<_>>()?;
 
         Ok(TimestampFinder {
+            formats,
+            timezone: Timezone::default(),
+            reference_date: Utc::now().date_naive(),
+            extractor: Extractor::default(),
+            occurrence: Occurrence::default(),
+            anchored: false,
+            range: TimeRange::default(),
+            filter: LineFilter::default(),
+            group_by: None,
+            value: None,
+            sketch_values: false,
+        })
+    }
+
+    fn compile(datetime_format: &str) -> Result<Format> {
+        let datetime_format = find_preset(datetime_format).map_or(datetime_format, |p| p.format);
+        let epoch_unit = EpochUnit::from_name(datetime_format);
+        let (regex, offset_source, missing_year, numeric) = match epoch_unit {
+            Some(unit) => (
+                Regex::new(&format!(r"\b{}\b", unit.regex()))?,
+                OffsetSource::Embedded,
+                false,
+                true,
+            ),
+            None => {
+                let offset_source = Self::offset_source(datetime_format)?;
+                let datetime_regex = Self::strftime_to_regex(datetime_format)?;
+                let tokens = Self::tokenize(datetime_format)?;
+                let missing_year = !Self::has_specifier(&tokens, YEAR_SPECIFIERS);
+                let numeric = Self::has_specifier(&tokens, &["s"]);
+                (
+                    Regex::new(&datetime_regex)?,
+                    offset_source,
+                    missing_year,
+                    numeric,
+                )
+            }
+        };
+
+        Ok(Format {
             datetime_format: datetime_format.to_string(),
             regex,
+            offset_source,
+            missing_year,
+            epoch_unit,
+            numeric,
         })
     }
 
+    /// Sets the timezone for timestamps that don't include a UTC offset. Defaults to UTC.
+    pub fn with_timezone(mut self, timezone: Timezone) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    /// Sets the date used to infer the year when the format doesn't include one, as in RFC 3164
+    /// syslog. Such timestamps are placed in the year of `date`, unless that would put them after
+    /// `date`, in which case they belong to the year before. Defaults to today.
+    pub fn with_reference_date(mut self, date: NaiveDate) -> Self {
+        self.reference_date = date;
+        self
+    }
+
+    /// Whether the year of some timestamps has to be inferred because the format leaves it out.
+    pub fn infers_year(&self) -> bool {
+        self.formats.iter().any(|format| format.missing_year)
+    }
+
+    /// Sets the part of each line that timestamps are read from. Defaults to the whole line.
+    pub fn with_extractor(mut self, extractor: Extractor) -> Self {
+        self.extractor = extractor;
+        self
+    }
+
+    /// Sets which timestamp to use on lines that contain several. Defaults to the first one.
+    pub fn with_occurrence(mut self, occurrence: Occurrence) -> Self {
+        self.occurrence = occurrence;
+        self
+    }
+
+    /// Only accepts timestamps that start at the beginning of the line, or of the part of it that
+    /// the extractor selects.
+    pub fn with_anchor(mut self, anchored: bool) -> Self {
+        self.anchored = anchored;
+        self
+    }
+
+    /// Only accepts timestamps within `range`. Defaults to accepting all of them.
+    pub fn with_range(mut self, range: TimeRange) -> Self {
+        self.range = range;
+        self
+    }
+
+    /// Only searches the lines that `filter` accepts. Defaults to searching all of them.
+    pub fn with_filter(mut self, filter: LineFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    /// Sorts lines into groups by what `regex` captures on them, like the status code of a request.
+    /// The key is the text of the first capture group that participates in the match, or of the
+    /// whole match if none does.
+    pub fn with_group_by(mut self, regex: Regex) -> Self {
+        self.group_by = Some(regex);
+        self
+    }
+
+    /// Finds the key of the group that a log line belongs to, if the finder groups lines and the
+    /// line has one.
+    pub fn group<'a>(&self, line: &'a str) -> Option<&'a str> {
+        Self::capture(self.group_by.as_ref()?, line)
+    }
+
+    /// Reads a number from each line with `regex`, like a response time or a size in bytes, so
+    /// that lines can be aggregated by value rather than counted. The number is the text of the
+    /// first capture group, or of the whole match if it has none.
+    pub fn with_value(mut self, regex: Regex) -> Self {
+        self.value = Some(regex);
+        self
+    }
+
+    /// Whether the finder reads a number from each line.
+    pub fn reads_values(&self) -> bool {
+        self.value.is_some()
+    }
+
+    /// Counts the numbers that the finder reads in a [`SketchTimeline`](crate::SketchTimeline)
+    /// rather than keeping each of them, so that a scan of a large log only has to keep enough of
+    /// them to estimate percentiles. Defaults to keeping them.
+    pub fn with_sketched_values(mut self, sketched: bool) -> Self {
+        self.sketch_values = sketched;
+        self
+    }
+
+    /// Whether the numbers that the finder reads are counted in sketches.
+    pub fn sketches_values(&self) -> bool {
+        self.sketch_values
+    }
+
+    /// Finds the number in a log line, if the finder reads values and the line has one.
+    pub fn value(&self, line: &str) -> Option<f64> {
+        let value = Self::capture(self.value.as_ref()?, line)?;
+        value.parse().ok().filter(|value: &f64| value.is_finite())
+    }
+
+    fn capture<'a>(regex: &Regex, line: &'a str) -> Option<&'a str> {
+        let captures = regex.captures(line)?;
+        let text = match captures.len() {
+            1 => captures.get(0),
+            _ => captures.iter().skip(1).flatten().next(),
+        };
+        text.map(|text| text.as_str())
+    }
+
+    /// Finds the timestamp in a log line, in the part of it that the extractor selects.
+    pub fn scan_line(&self, line: &str) -> ScannedLine {
+        if !self.filter.accepts(line) {
+            return ScannedLine::Filtered;
+        }
+        let scanned = match self.extractor.extract(line) {
+            Field::Text(text) => self.search(&text),
+            Field::Number(number) => self
+                .number_to_nanos(&number)
+                .map_or(ScannedLine::Unparseable, ScannedLine::Timestamp),
+            Field::Missing => ScannedLine::NoTimestamp,
+            Field::Malformed => ScannedLine::Malformed,
+        };
+        match scanned {
+            ScannedLine::Timestamp(timestamp) if !self.range.contains(timestamp) => {
+                ScannedLine::OutOfRange(timestamp)
+            }
+            scanned => scanned,
+        }
+    }
+
+    /// Reads a number from a structured log as a UNIX timestamp. Fractional numbers are seconds.
+    /// Integers are in the unit of the first epoch format, if there is one, or else in the unit
+    /// that their magnitude suggests.
+    fn number_to_nanos(&self, number: &str) -> Option<i64> {
+        if number.contains(['e', 'E']) {
+            let seconds: f64 = number.parse().ok()?;
+            return Some((seconds * 1e9) as i64);
+        }
+        let unit = match self.formats.iter().find_map(|format| format.epoch_unit) {
+            _ if number.contains('.') => EpochUnit::FractionalSeconds,
+            Some(unit) if unit != EpochUnit::FractionalSeconds => unit,
+            _ => EpochUnit::for_digits(number.trim_start_matches('-').len()),
+        };
+        unit.to_nanos(number)
+    }
+
+    /// Finds a timestamp in `s` and returns it in nanoseconds since the UNIX epoch. Which one is
+    /// used if `s` contains several depends on the occurrence and the anchor.
     pub fn find_timestamp(&self, s: &str) -> Option<i64> {
-        let regex_match = self.regex.captures(s)?.get(0)?;
-        let datetime =
-            NaiveDateTime::parse_from_str(regex_match.as_str(), &self.datetime_format).ok()?;
-        Some(datetime.and_utc().timestamp())
+        match self.search(s) {
+            ScannedLine::Timestamp(timestamp) => Some(timestamp),
+            _ => None,
+        }
+    }
+
+    fn search(&self, s: &str) -> ScannedLine {
+        let mut result = ScannedLine::NoTimestamp;
+        for format in &self.formats {
+            match self.search_format(format, s) {
+                ScannedLine::Timestamp(timestamp) => return ScannedLine::Timestamp(timestamp),
+                ScannedLine::Unparseable => result = ScannedLine::Unparseable,
+                _ => {}
+            }
+        }
+        result
+    }
+
+    fn search_format(&self, format: &Format, s: &str) -> ScannedLine {
+        // The leftmost match starts at the beginning of `s` if any match does
+        let limit = if self.anchored { 1 } else { usize::MAX };
+        let mut failures = 0;
+        let timestamp = {
+            let mut timestamps = format
+                .regex
+                .captures_iter(s)
+                .take(limit)
+                .filter(|captures| {
+                    !self.anchored || captures.get(0).is_some_and(|m| m.start() == 0)
+                })
+                .filter(|captures| {
+                    !format.numeric || captures.get(0).is_some_and(|m| is_whole_number(s, m))
+                })
+                .filter_map(|captures| {
+                    let timestamp = self.parse_match(format, &captures);
+                    failures += usize::from(timestamp.is_none());
+                    timestamp
+                });
+            match self.occurrence {
+                Occurrence::First => timestamps.next(),
+                Occurrence::Last => timestamps.last(),
+                Occurrence::Nth(n) => timestamps.nth(n.saturating_sub(1)),
+            }
+        };
+        match timestamp {
+            Some(timestamp) => ScannedLine::Timestamp(timestamp),
+            None if failures > 0 => ScannedLine::Unparseable,
+            None => ScannedLine::NoTimestamp,
+        }
+    }
+
+    fn parse_match(&self, format: &Format, captures: &Captures) -> Option<i64> {
+        if let Some(unit) = format.epoch_unit {
+            return unit.to_nanos(captures.get(0)?.as_str());
+        }
+
+        let datetime = if format.missing_year {
+            // Allow for a day of slack because the reference date is in UTC and the log might not be
+            let latest = self.reference_date + TimeDelta::days(1);
+            let year = self.reference_date.year();
+            [year, year - 1]
+                .into_iter()
+                .filter_map(|year| self.parse_captures(format, captures, Some(year)))
+                .find(|datetime| datetime.date_naive() <= latest)?
+        } else {
+            self.parse_captures(format, captures, None)?
+        };
+        datetime.timestamp_nanos_opt()
+    }
+
+    fn parse_captures(
+        &self,
+        format: &Format,
+        captures: &Captures,
+        year: Option<i32>,
+    ) -> Option<DateTime<Utc>> {
+        let regex_match = captures.get(0)?;
+        let mut text = Cow::Borrowed(regex_match.as_str());
+        let mut datetime_format = Cow::Borrowed(format.datetime_format.as_str());
+        let mut timezone = self.timezone;
+
+        if let OffsetSource::Name { parse_format } = &format.offset_source {
+            let name = captures.name(TIMEZONE_NAME_GROUP)?;
+            let start = name.start() - regex_match.start();
+            let end = name.end() - regex_match.start();
+            text = Cow::Owned(format!("{}{}", &text[..start], &text[end..]));
+            datetime_format = Cow::Borrowed(parse_format);
+            timezone = Timezone::from_name(name.as_str()).unwrap_or(self.timezone);
+        }
+
+        if let Some(year) = year {
+            text = Cow::Owned(format!("{} {}", text, year));
+            datetime_format = Cow::Owned(format!("{} %Y", datetime_format));
+        }
+
+        match format.offset_source {
+            OffsetSource::Embedded => Some(
+                DateTime::parse_from_str(&text, &datetime_format)
+                    .ok()?
+                    .with_timezone(&Utc),
+            ),
+            _ => {
+                Some(timezone.to_utc(&NaiveDateTime::parse_from_str(&text, &datetime_format).ok()?))
+            }
+        }
+    }
+
+    fn offset_source(time_format: &str) -> Result<OffsetSource> {
+        let tokens = Self::tokenize(time_format)?;
+        if Self::has_specifier(&tokens, &["z", ":z", "::z", ":::z", "#z", "+", "s"]) {
+            return Ok(OffsetSource::Embedded);
+        }
+        let name_count = tokens
+            .iter()
+            .filter(|token| **token == FormatToken::Specifier("%Z"))
+            .count();
+        match name_count {
+            0 => Ok(OffsetSource::Assumed),
+            1 => {
+                let parse_format = tokens
+                    .iter()
+                    .map(|token| match token {
+                        FormatToken::Literal(text) => *text,
+                        FormatToken::Whitespace => " ",
+                        FormatToken::Specifier("%Z") => "",
+                        FormatToken::Specifier(specifier) => *specifier,
+                    })
+                    .collect();
+                Ok(OffsetSource::Name { parse_format })
+            }
+            _ => Err(anyhow!(
+                "timestamp format '{}' contains more than one '%Z'",
+                time_format
+            )),
+        }
+    }
+
+    /// Checks whether any of the specifiers in `tokens` is one of `names`, ignoring padding modifiers.
+    fn has_specifier(tokens: &[FormatToken], names: &[&str]) -> bool {
+        tokens.iter().any(|token| match token {
+            FormatToken::Specifier(specifier) => {
+                let body = &specifier[1..];
+                let name = body.strip_prefix(['-', '_', '0']).unwrap_or(body);
+                names.contains(&name)
+            }
+            _ => false,
+        })
+    }
+
+    fn strftime_to_regex(time_format: &str) -> Result<String> {
+        let mut regex = String::new();
+        for token in Self::tokenize(time_format)? {
+            match token {
+                FormatToken::Literal(text) => regex.push_str(&regex::escape(text)),
+                FormatToken::Whitespace => regex.push_str(r"\s+"),
+                FormatToken::Specifier(specifier) => {
+                    regex.push_str(&Self::specifier_to_regex(specifier)?)
+                }
+            }
+        }
+        Ok(regex)
+    }
+
+    fn tokenize(time_format: &str) -> Result<Vec<FormatToken<'_>>> {
+        let mut tokens = Vec::new();
+        let mut rest = time_format;
+        while !rest.is_empty() {
+            let (token, len) = if rest.starts_with('%') {
+                let len = Self::specifier_len(rest).ok_or_else(|| {
+                    anyhow!(
+                        "timestamp format '{}' ends with an incomplete specifier '{}'",
+                        time_format,
+                        rest
+                    )
+                })?;
+                (FormatToken::Specifier(&rest[..len]), len)
+            } else if rest.starts_with(char::is_whitespace) {
+                let len = rest
+                    .find(|c: char| !c.is_whitespace())
+                    .unwrap_or(rest.len());
+                (FormatToken::Whitespace, len)
+            } else {
+                let len = rest
+                    .find(|c: char| c == '%' || c.is_whitespace())
+                    .unwrap_or(rest.len());
+                (FormatToken::Literal(&rest[..len]), len)
+            };
+            tokens.push(token);
+            rest = &rest[len..];
+        }
+        Ok(tokens)
     }
 
-    fn strftime_to_regex(time_format: &str) -> String {
-        time_format
-            .replace("%Y", r"\d{1,4}")
-            .replace("%C", r"\d{1,2}")
-            .replace("%y", r"\d{1,2")
-            .replace("%m", r"\d{1,2}")
-            .replace("%b", r"[A-Za-z]{3}")
-            .replace("%B", r"[A-Za-z]{3,4,5,6,7,8,9}")
-            .replace("%h", r"[A-Za-z]{3}")
-            .replace("%d", r"\d{1,2}")
-            .replace("%H", r"\d{1,2}")
-            .replace("%M", r"\d{1,2}")
-            .replace("%S", r"\d{1,2}")
-            .replace("%.f", r"\d{1,}")
-            .replace("%s", r"\d{1,10}")
-        // TODO: Add support for remaining characters. https://docs.rs/chrono/0.4.13/chrono/format/strftime/index.html
+    /// Returns the length of the specifier at the start of `s`, which must begin with `%`.
+    fn specifier_len(s: &str) -> Option<usize> {
+        let body = &s[1..];
+        let modifier_len = usize::from(body.starts_with(['-', '_', '0']));
+        let spec = &body[modifier_len..];
+        let spec_len = match MULTI_CHAR_SPECIFIERS.iter().find(|m| spec.starts_with(*m)) {
+            Some(m) => m.len(),
+            None => spec.chars().next()?.len_utf8(),
+        };
+        Some(1 + modifier_len + spec_len)
+    }
+
+    fn specifier_to_regex(specifier: &str) -> Result<String> {
+        let body = &specifier[1..];
+        let (modifier, spec) = match body.strip_prefix(['-', '_', '0']) {
+            Some(spec) => (&body[..1], spec),
+            None => ("", body),
+        };
+
+        // Composite specifiers expand to the equivalent sequence of simpler ones.
+        let expansion = match spec {
+            "D" | "x" => Some("%m/%d/%y"),
+            "F" => Some("%Y-%m-%d"),
+            "v" => Some("%e-%b-%Y"),
+            "R" => Some("%H:%M"),
+            "T" | "X" => Some("%H:%M:%S"),
+            "r" => Some("%I:%M:%S %p"),
+            "c" => Some("%a %b %e %H:%M:%S %Y"),
+            _ => None,
+        };
+        if let Some(expansion) = expansion {
+            if !modifier.is_empty() {
+                return Err(Self::unsupported_specifier(specifier));
+            }
+            return Self::strftime_to_regex(expansion);
+        }
+
+        let numeric = match spec {
+            "Y" | "G" => Some(r"[+-]?\d{1,4}"),
+            "C" | "y" | "g" | "m" | "d" | "U" | "W" | "V" | "H" | "I" | "M" | "S" => {
+                Some(r"\d{1,2}")
+            }
+            "e" | "k" | "l" => Some(r" ?\d{1,2}"),
+            "q" => Some("[1-4]"),
+            "w" => Some("[0-6]"),
+            "u" => Some("[1-7]"),
+            "j" => Some(r"\d{1,3}"),
+            "f" => Some(r"\d{1,9}"),
+            "s" => Some(EpochUnit::Seconds.regex()),
+            _ => None,
+        };
+        if let Some(numeric) = numeric {
+            let padding = if modifier == "_" { " *" } else { "" };
+            return Ok(format!("{}{}", padding, numeric));
+        }
+
+        if !modifier.is_empty() {
+            return Err(Self::unsupported_specifier(specifier));
+        }
+        let regex = match spec {
+            "b" | "h" => "(?i:jan|feb|mar|apr|may|jun|jul|aug|sep|oct|nov|dec)",
+            "B" => concat!(
+                "(?i:january|february|march|april|may|june|july|august|september|october|",
+                "november|december|jan|feb|mar|apr|jun|jul|aug|sep|oct|nov|dec)"
+            ),
+            "a" => "(?i:mon|tue|wed|thu|fri|sat|sun)",
+            "A" => concat!(
+                "(?i:monday|tuesday|wednesday|thursday|friday|saturday|sunday|",
+                "mon|tue|wed|thu|fri|sat|sun)"
+            ),
+            "p" | "P" => "(?i:am|pm)",
+            ".f" | ".3f" | ".6f" | ".9f" => r"(?:\.\d+)?",
+            "3f" => r"\d{3}",
+            "6f" => r"\d{6}",
+            "9f" => r"\d{9}",
+            "Z" => {
+                return Ok(format!(
+                    r"(?P<{}>[A-Za-z][A-Za-z0-9_/+\-]*)",
+                    TIMEZONE_NAME_GROUP
+                ))
+            }
+            "z" | ":z" => r"[+-]\d{2}:?\d{2}",
+            "::z" => r"[+-]\d{2}:?\d{2}(?::?\d{2})?",
+            ":::z" => r"[+-]\d{2}(?::?\d{2})?",
+            "#z" => r"(?:[+-]\d{2}(?::?\d{2})?|[Zz])",
+            "+" => {
+                r"\d{4}-\d{2}-\d{2}[Tt ]\d{2}:\d{2}:\d{2}(?:\.\d+)?(?:[Zz]|UTC|[+-]\d{2}:?\d{2})"
+            }
+            "t" | "n" => r"\s+",
+            "%" => "%",
+            _ => return Err(Self::unsupported_specifier(specifier)),
+        };
+        Ok(regex.to_string())
+    }
+
+    fn unsupported_specifier(specifier: &str) -> anyhow::Error {
+        anyhow!("unsupported timestamp format specifier '{}'", specifier)
     }
 }
 
@@ -49,18 +718,71 @@ fn timestamp_finder() {
     let date_finder = TimestampFinder::new(format).unwrap();
     let log = "Nov 23 06:26:40 ip-10-1-26-81 haproxy[20128]: 54.242.135.245:57305 [23/Nov/2019:06:26:40.781] public repackager/i-05fa49c0e7db8c328 0/0/0/78/78 206 913/458 - - ---- 9/9/6/0/0 0/0 {1.1 v1-akamaitech.net(ghost) (AkamaiGHost), 1.1 v1-akamaitech.net(ghost) (AkamaiGHost), 1.1 akamai.n|bytes=0-0} {||1|bytes 0-0/499704} \"GET /deliveries/2518cb13a48bdf53b2f936f44e7042a3cc7baa06.m3u8/seg-88-v1-a1.ts HTTP/1.1\"";
     let timestamp = date_finder.find_timestamp(log).unwrap();
-    assert_eq!(timestamp, 1574490400);
+    assert_eq!(timestamp, 1_574_490_400_781_000_000);
 }
 
 #[test]
 fn timestamp_finder_strftime_to_regex() {
     let convert_compile_match = |format: &str, match_str: &str| {
-        let format_regex = TimestampFinder::strftime_to_regex(format);
+        let format_regex = TimestampFinder::strftime_to_regex(format).unwrap();
         let regex = Regex::new(&format_regex).unwrap();
         assert!(regex.is_match(match_str));
     };
 
     convert_compile_match("%d/%b/%Y:%H:%M:%S%.f", "06/Jan/2006:13:04:05.000");
+    convert_compile_match("%y-%m-%d", "06-01-02");
+    convert_compile_match("%B %d", "January 02");
+    convert_compile_match("%a %b %e %T %Y", "Mon Jan  2 15:04:05 2006");
+    convert_compile_match("%F %I:%M %p", "2006-01-02 03:04 PM");
+    convert_compile_match("%Y.%j", "2006.002");
+    convert_compile_match("%FT%T%z", "2006-01-02T15:04:05-0700");
+    convert_compile_match("%FT%T%:z", "2006-01-02T15:04:05-07:00");
+    convert_compile_match("%T,%3f", "15:04:05,123");
+    convert_compile_match("%H%%%M", "15%04");
+    convert_compile_match("(%H)", "(15)");
+}
+
+#[test]
+fn timestamp_finder_strftime_to_regex_rejects_unknown_specifiers() {
+    let error = TimestampFinder::strftime_to_regex("%Y-%Q").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "unsupported timestamp format specifier '%Q'"
+    );
+
+    let error = TimestampFinder::strftime_to_regex("%H:%M:%").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "timestamp format '%H:%M:%' ends with an incomplete specifier '%'"
+    );
+
+    assert!(TimestampFinder::new("%_b").is_err());
+}
+
+#[test]
+fn timestamp_finder_extended_specifiers() {
+    let find = |format: &str, log: &str| TimestampFinder::new(format).unwrap().find_timestamp(log);
+
+    assert_eq!(
+        find("%a %b %e %T %Y", "[Mon Jan  2 15:04:05 2006] error"),
+        Some(1_136_214_245_000_000_000)
+    );
+    assert_eq!(
+        find("%F %I:%M:%S %p", "at 2006-01-02 03:04:05 PM: started"),
+        Some(1_136_214_245_000_000_000)
+    );
+    assert_eq!(
+        find("%Y%j %T", "ts=2006002 15:04:05"),
+        Some(1_136_214_245_000_000_000)
+    );
+    assert_eq!(
+        find("%F %T.%3f", "2006-01-02 15:04:05.123 INFO"),
+        Some(1_136_214_245_123_000_000)
+    );
+    assert_eq!(
+        find("%d%%%m%%%Y %R", "02%01%2006 15:04"),
+        Some(1_136_214_240_000_000_000)
+    );
 }
 
 #[test]
@@ -71,10 +793,475 @@ fn timestamp_finder_epochseconds() {
     // Full 10 digit recent epoch
     let log = "1621568291 ip-10-1-26-81 haproxy[20128]: 54.242.135...";
     let timestamp = date_finder.find_timestamp(log).unwrap();
-    assert_eq!(timestamp, 1621568291);
+    assert_eq!(timestamp, 1_621_568_291_000_000_000);
 
     // Shorter timestamp (15th Jan 1970)
     let log = "1234567 ip-10-1-26-81 haproxy[20128]: 54.242.135...";
     let timestamp = date_finder.find_timestamp(log).unwrap();
-    assert_eq!(timestamp, 1234567);
+    assert_eq!(timestamp, 1_234_567_000_000_000);
+}
+
+#[test]
+fn timestamp_finder_numeric_offsets() {
+    let format = "%d/%b/%Y:%H:%M:%S %z";
+    let date_finder = TimestampFinder::new(format).unwrap();
+
+    // The same instant, logged by hosts in different zones
+    let log = "10.1.1.10 - - [23/Nov/2019:06:26:40 +0000] \"GET / HTTP/1.1\" 200 913";
+    assert_eq!(
+        date_finder.find_timestamp(log),
+        Some(1_574_490_400_000_000_000)
+    );
+    let log = "10.1.1.11 - - [23/Nov/2019:01:26:40 -0500] \"GET / HTTP/1.1\" 200 913";
+    assert_eq!(
+        date_finder.find_timestamp(log),
+        Some(1_574_490_400_000_000_000)
+    );
+    let log = "10.1.1.12 - - [23/Nov/2019:12:11:40 +0545] \"GET / HTTP/1.1\" 200 913";
+    assert_eq!(
+        date_finder.find_timestamp(log),
+        Some(1_574_490_400_000_000_000)
+    );
+
+    // An explicit offset wins over the assumed timezone
+    let date_finder = TimestampFinder::new("%FT%T%:z")
+        .unwrap()
+        .with_timezone("Asia/Tokyo".parse().unwrap());
+    let log = "2019-11-23T07:26:40+01:00 level=info msg=started";
+    assert_eq!(
+        date_finder.find_timestamp(log),
+        Some(1_574_490_400_000_000_000)
+    );
+}
+
+#[test]
+fn timestamp_finder_timezone_names() {
+    let date_finder = TimestampFinder::new("%a %b %e %T %Z %Y").unwrap();
+    let log = "Sat Nov 23 01:26:40 EST 2019 backup started";
+    assert_eq!(
+        date_finder.find_timestamp(log),
+        Some(1_574_490_400_000_000_000)
+    );
+    let log = "Sat Nov 23 06:26:40 UTC 2019 backup started";
+    assert_eq!(
+        date_finder.find_timestamp(log),
+        Some(1_574_490_400_000_000_000)
+    );
+
+    let date_finder = TimestampFinder::new("[%F %T %Z]").unwrap();
+    let log = "[2019-11-23 07:26:40 CET] backup started";
+    assert_eq!(
+        date_finder.find_timestamp(log),
+        Some(1_574_490_400_000_000_000)
+    );
+    let log = "[2019-11-23 07:26:40 Europe/Paris] backup started";
+    assert_eq!(
+        date_finder.find_timestamp(log),
+        Some(1_574_490_400_000_000_000)
+    );
+
+    // Names that can't be resolved fall back to the assumed timezone
+    let log = "[2019-11-23 11:56:40 IST] backup started";
+    assert_eq!(
+        date_finder.find_timestamp(log),
+        Some(1_574_510_200_000_000_000)
+    );
+    let date_finder = date_finder.with_timezone("+05:30".parse().unwrap());
+    assert_eq!(
+        date_finder.find_timestamp(log),
+        Some(1_574_490_400_000_000_000)
+    );
+
+    assert!(TimestampFinder::new("%Z %T %Z").is_err());
+}
+
+#[test]
+fn timestamp_finder_assumed_timezone() {
+    let format = "%F %T";
+    let log = "2019-11-23 01:26:40 backup started";
+
+    let date_finder = TimestampFinder::new(format).unwrap();
+    assert_eq!(
+        date_finder.find_timestamp(log),
+        Some(1_574_472_400_000_000_000)
+    );
+
+    let date_finder = TimestampFinder::new(format)
+        .unwrap()
+        .with_timezone("America/New_York".parse().unwrap());
+    assert_eq!(
+        date_finder.find_timestamp(log),
+        Some(1_574_490_400_000_000_000)
+    );
+
+    // UNIX timestamps are always UTC
+    let date_finder = TimestampFinder::new("%s")
+        .unwrap()
+        .with_timezone("America/New_York".parse().unwrap());
+    assert_eq!(
+        date_finder.find_timestamp("1574490400 started"),
+        Some(1_574_490_400_000_000_000)
+    );
+}
+
+#[test]
+fn timestamp_finder_presets() {
+    let date_finder = TimestampFinder::new("clf").unwrap();
+    let log = "10.1.1.11 - - [23/Nov/2019:01:26:40 -0500] \"GET / HTTP/1.1\" 200 913";
+    assert_eq!(
+        date_finder.find_timestamp(log),
+        Some(1_574_490_400_000_000_000)
+    );
+
+    // Only the accept date in brackets counts, not the syslog prefix
+    let date_finder = TimestampFinder::new("haproxy").unwrap();
+    let log = "Nov 23 06:26:40 ip-10-1-26-81 haproxy[20128]: 54.242.135.245:57305 [23/Nov/2019:06:26:41.781] public";
+    assert_eq!(
+        date_finder.find_timestamp(log),
+        Some(1_574_490_401_781_000_000)
+    );
+}
+
+#[test]
+fn timestamp_finder_infers_missing_year() {
+    let log = "Nov 23 06:26:40 ip-10-1-1-1 haproxy[20128]: 10.1.1.10:57305 [23/Nov/2019:06:26:41.781] public";
+    let date_finder = TimestampFinder::new("syslog")
+        .unwrap()
+        .with_reference_date(NaiveDate::from_ymd_opt(2019, 12, 31).unwrap());
+    assert_eq!(
+        date_finder.find_timestamp(log),
+        Some(1_574_490_400_000_000_000)
+    );
+
+    // A log that was last written in January started in the previous year
+    let date_finder = TimestampFinder::new("%b %e %H:%M:%S")
+        .unwrap()
+        .with_reference_date(NaiveDate::from_ymd_opt(2020, 1, 2).unwrap());
+    assert_eq!(
+        date_finder.find_timestamp(log),
+        Some(1_574_490_400_000_000_000)
+    );
+    let log = "Jan  2 00:00:00 ip-10-1-1-1 cron[1]: (root) CMD (run-parts /etc/cron.daily)";
+    assert_eq!(
+        date_finder.find_timestamp(log),
+        Some(1_577_923_200_000_000_000)
+    );
+
+    // February 29th only exists in leap years
+    let log = "Feb 29 12:00:00 ip-10-1-1-1 cron[1]: (root) CMD (run-parts /etc/cron.daily)";
+    let date_finder = TimestampFinder::new("syslog")
+        .unwrap()
+        .with_reference_date(NaiveDate::from_ymd_opt(2022, 6, 1).unwrap());
+    assert_eq!(date_finder.find_timestamp(log), None);
+    let date_finder = date_finder.with_reference_date(NaiveDate::from_ymd_opt(2021, 1, 1).unwrap());
+    assert_eq!(
+        date_finder.find_timestamp(log),
+        Some(1_582_977_600_000_000_000)
+    );
+}
+
+#[test]
+fn timestamp_finder_epoch_units() {
+    let find = |format: &str, log: &str| TimestampFinder::new(format).unwrap().find_timestamp(log);
+
+    // Java, JavaScript, Kafka
+    assert_eq!(
+        find(
+            "epoch_ms",
+            "1621568291123 ip-10-1-26-81 haproxy[20128]: 54.242.135..."
+        ),
+        Some(1_621_568_291_123_000_000)
+    );
+    assert_eq!(
+        find("epoch_us", "ts=1621568291123456 msg=started"),
+        Some(1_621_568_291_123_456_000)
+    );
+    // Go, OpenTelemetry
+    assert_eq!(
+        find(
+            "epoch_ns",
+            "{\"ts\":1621568291123456789,\"msg\":\"started\"}"
+        ),
+        Some(1_621_568_291_123_456_789)
+    );
+    assert_eq!(
+        find("epoch_frac", "1621568291.123 10.1.1.10 TCP_MISS/200 GET /"),
+        Some(1_621_568_291_123_000_000)
+    );
+    assert_eq!(
+        find(
+            "epoch_frac",
+            "1621568291.123456789 10.1.1.10 TCP_MISS/200 GET /"
+        ),
+        Some(1_621_568_291_123_456_789)
+    );
+
+    // Numbers with more digits than the unit allows aren't truncated
+    assert_eq!(find("epoch_ms", "1621568291123456789 started"), None);
+    assert_eq!(find("epoch_ns", "92233720368547758070 started"), None);
+    assert_eq!(find("%s", "1621568291123 started"), None);
+    assert_eq!(find("epoch", "1621568291123456789 started"), None);
+
+    // Nor are other numbers mistaken for timestamps
+    assert_eq!(find("epoch_ms", "pid 20128 started"), None);
+    assert_eq!(
+        find("%s", "10.1.1.10 1621568291 TCP_MISS/200 GET /"),
+        Some(1_621_568_291_000_000_000)
+    );
+    assert_eq!(
+        find("epoch_ms", "10.1.1.10 1621568291123 TCP_MISS/200 GET /"),
+        Some(1_621_568_291_123_000_000)
+    );
+    assert_eq!(
+        find("epoch_frac", "10.1.1.10 1621568291.123 TCP_MISS/200 GET /"),
+        Some(1_621_568_291_123_000_000)
+    );
+}
+
+#[test]
+fn timestamp_finder_json_field() {
+    let date_finder = TimestampFinder::new("rfc3339")
+        .unwrap()
+        .with_extractor(Extractor::json_field("ts"));
+
+    // The nested created_at would be found first when searching the whole line
+    assert_eq!(
+        date_finder.scan_line(
+            r#"{"user":{"created_at":"2019-11-23T06:26:40Z"},"ts":"2024-01-02T03:04:05Z"}"#
+        ),
+        ScannedLine::Timestamp(1_704_164_645_000_000_000)
+    );
+    assert_eq!(
+        date_finder.scan_line(r#"{"ts":1704164645}"#),
+        ScannedLine::Timestamp(1_704_164_645_000_000_000)
+    );
+    assert_eq!(
+        date_finder.scan_line(r#"{"ts":1704164645123}"#),
+        ScannedLine::Timestamp(1_704_164_645_123_000_000)
+    );
+    assert_eq!(
+        date_finder.scan_line(r#"{"ts":1704164645123456789}"#),
+        ScannedLine::Timestamp(1_704_164_645_123_456_789)
+    );
+    assert_eq!(
+        date_finder.scan_line(r#"{"ts":1704164645.25}"#),
+        ScannedLine::Timestamp(1_704_164_645_250_000_000)
+    );
+    assert_eq!(
+        date_finder.scan_line(r#"{"ts":-1.5}"#),
+        ScannedLine::Timestamp(-1_500_000_000)
+    );
+    assert_eq!(
+        date_finder.scan_line(r#"{"ts":-0.25}"#),
+        ScannedLine::Timestamp(-250_000_000)
+    );
+    assert_eq!(
+        date_finder.scan_line(r#"{"ts":"yesterday"}"#),
+        ScannedLine::NoTimestamp
+    );
+    assert_eq!(
+        date_finder.scan_line(r#"{"time":"2024-01-02T03:04:05Z"}"#),
+        ScannedLine::NoTimestamp
+    );
+    assert_eq!(
+        date_finder.scan_line("2024-01-02T03:04:05Z not JSON"),
+        ScannedLine::Malformed
+    );
+
+    // An epoch format decides the unit of numbers, which may be too small to guess it
+    let date_finder = TimestampFinder::new("epoch_ms")
+        .unwrap()
+        .with_extractor(Extractor::json_field("ts"));
+    assert_eq!(
+        date_finder.scan_line(r#"{"ts":86400000}"#),
+        ScannedLine::Timestamp(86_400_000_000_000)
+    );
+}
+
+#[test]
+fn timestamp_finder_logfmt_key() {
+    let date_finder = TimestampFinder::new("rfc3339")
+        .unwrap()
+        .with_extractor(Extractor::logfmt_key(None));
+    assert_eq!(
+        date_finder.scan_line(
+            r#"level=info ts=2024-01-02T03:04:05.5Z msg="started at 2019-11-23T06:26:40Z""#
+        ),
+        ScannedLine::Timestamp(1_704_164_645_500_000_000)
+    );
+    assert_eq!(
+        date_finder.scan_line("level=info ts=1704164645123 msg=started"),
+        ScannedLine::Timestamp(1_704_164_645_123_000_000)
+    );
+    assert_eq!(
+        date_finder.scan_line(r#"level=info msg="started"#),
+        ScannedLine::Malformed
+    );
+}
+
+#[test]
+fn timestamp_finder_timestamp_regex() {
+    // Searching the whole line finds the syslog prefix first
+    let log = "Nov 23 06:26:40 ip-10-1-1-1 haproxy[20128]: 10.1.1.10:57305 [23/Nov/2019:06:26:40.781] public myapp/i-05fa49c0e7db8c328 0/0/0/78/78 206 913/458";
+    let date_finder = TimestampFinder::new("%b %d %H:%M:%S%.f")
+        .unwrap()
+        .with_reference_date(NaiveDate::from_ymd_opt(2019, 12, 31).unwrap());
+    assert_eq!(
+        date_finder.find_timestamp(log),
+        Some(1_574_490_400_000_000_000)
+    );
+
+    let date_finder = TimestampFinder::new("%d/%b/%Y:%H:%M:%S%.f")
+        .unwrap()
+        .with_extractor(Extractor::timestamp_regex(r"\d \[(?P<ts>[^\]]+)\]").unwrap());
+    assert_eq!(
+        date_finder.scan_line(log),
+        ScannedLine::Timestamp(1_574_490_400_781_000_000)
+    );
+    assert_eq!(
+        date_finder.scan_line("Nov 23 06:26:40 ip-10-1-1-1 haproxy[20128]: Proxy started."),
+        ScannedLine::NoTimestamp
+    );
+}
+
+#[test]
+fn timestamp_finder_multiple_formats() {
+    let date_finder = TimestampFinder::from_formats(&["nginx", "iso8601-space"]).unwrap();
+    assert_eq!(
+        date_finder.find_timestamp(
+            r#"10.1.1.10 - - [02/Jan/2024:03:04:05 +0100] "GET / HTTP/1.1" 200 612"#
+        ),
+        Some(1_704_161_045_000_000_000)
+    );
+    assert_eq!(
+        date_finder.find_timestamp("2024-01-02 03:04:05 INFO started"),
+        Some(1_704_164_645_000_000_000)
+    );
+    assert_eq!(date_finder.find_timestamp("started"), None);
+
+    // The first format wins when several match
+    let date_finder =
+        TimestampFinder::from_formats(&["%Y-%m-%d %H:%M", "%Y-%m-%d %H:%M:%S"]).unwrap();
+    assert_eq!(
+        date_finder.find_timestamp("2024-01-02 03:04:05 INFO started"),
+        Some(1_704_164_640_000_000_000)
+    );
+
+    assert!(TimestampFinder::from_formats::<&str>(&[]).is_err());
+    assert!(TimestampFinder::from_formats(&["nginx", "%Q"]).is_err());
+}
+
+#[test]
+fn timestamp_finder_occurrence() {
+    let log = "2024-01-02T03:04:05Z GET / started=2024-01-02T03:04:01Z ended=2024-01-02T03:04:04Z";
+    let find = |occurrence: Occurrence| {
+        TimestampFinder::new("%Y-%m-%dT%H:%M:%S")
+            .unwrap()
+            .with_occurrence(occurrence)
+            .find_timestamp(log)
+    };
+    assert_eq!(find(Occurrence::First), Some(1_704_164_645_000_000_000));
+    assert_eq!(find(Occurrence::Nth(2)), Some(1_704_164_641_000_000_000));
+    assert_eq!(find(Occurrence::Last), Some(1_704_164_644_000_000_000));
+    assert_eq!(find(Occurrence::Nth(4)), None);
+
+    assert_eq!("first".parse::<Occurrence>().unwrap(), Occurrence::First);
+    assert_eq!("last".parse::<Occurrence>().unwrap(), Occurrence::Last);
+    assert_eq!("3".parse::<Occurrence>().unwrap(), Occurrence::Nth(3));
+    assert!("0".parse::<Occurrence>().is_err());
+    assert!("second".parse::<Occurrence>().is_err());
+}
+
+#[test]
+fn timestamp_finder_anchored() {
+    let date_finder = TimestampFinder::new("%Y-%m-%d %H:%M:%S")
+        .unwrap()
+        .with_anchor(true);
+    assert_eq!(
+        date_finder.find_timestamp("2024-01-02 03:04:05 INFO started"),
+        Some(1_704_164_645_000_000_000)
+    );
+    assert_eq!(
+        date_finder.find_timestamp("    at 2024-01-02 03:04:05 com.example.App.main"),
+        None
+    );
+}
+
+#[test]
+fn timestamp_finder_unparseable() {
+    let date_finder = TimestampFinder::new("clf").unwrap();
+    assert_eq!(
+        date_finder.scan_line("[31/Feb/2024:03:04:05 +0000] GET /"),
+        ScannedLine::Unparseable
+    );
+    assert_eq!(
+        date_finder.scan_line("[31/Feb/2024:03:04:05 +0000] [01/Feb/2024:03:04:05 +0000]"),
+        ScannedLine::Timestamp(1_706_756_645_000_000_000)
+    );
+    assert_eq!(date_finder.scan_line("GET /"), ScannedLine::NoTimestamp);
+}
+
+#[test]
+fn timestamp_finder_range() {
+    let date_finder = TimestampFinder::new("clf").unwrap().with_range(TimeRange {
+        since: Some(1_706_756_645_000_000_000),
+        until: None,
+    });
+    assert_eq!(
+        date_finder.scan_line("[01/Feb/2024:03:04:05 +0000] GET /"),
+        ScannedLine::Timestamp(1_706_756_645_000_000_000)
+    );
+    assert_eq!(
+        date_finder.scan_line("[01/Feb/2024:03:04:04 +0000] GET /"),
+        ScannedLine::OutOfRange(1_706_756_644_000_000_000)
+    );
+    assert_eq!(date_finder.scan_line("GET /"), ScannedLine::NoTimestamp);
+}
+
+#[test]
+fn timestamp_finder_filter() {
+    let filter = LineFilter::new(&["GET"], &["healthcheck"]).unwrap();
+    let date_finder = TimestampFinder::new("clf").unwrap().with_filter(filter);
+    assert_eq!(
+        date_finder.scan_line("[01/Feb/2024:03:04:05 +0000] GET /"),
+        ScannedLine::Timestamp(1_706_756_645_000_000_000)
+    );
+    assert_eq!(
+        date_finder.scan_line("[01/Feb/2024:03:04:05 +0000] GET /healthcheck"),
+        ScannedLine::Filtered
+    );
+    assert_eq!(
+        date_finder.scan_line("[01/Feb/2024:03:04:05 +0000] POST /"),
+        ScannedLine::Filtered
+    );
+}
+
+#[test]
+fn timestamp_finder_group() {
+    let date_finder = TimestampFinder::new("clf").unwrap();
+    assert_eq!(date_finder.group("GET / 503"), None);
+
+    let date_finder = date_finder.with_group_by(Regex::new(r" (\d)\d\d$").unwrap());
+    assert_eq!(date_finder.group("GET / 503"), Some("5"));
+    assert_eq!(date_finder.group("starting up"), None);
+
+    let date_finder = date_finder.with_group_by(Regex::new(r"(?:(ERROR)|(WARN))").unwrap());
+    assert_eq!(date_finder.group("level WARN"), Some("WARN"));
+
+    let date_finder = date_finder.with_group_by(Regex::new(r"\b[A-Z]{4,5}\b").unwrap());
+    assert_eq!(date_finder.group("level ERROR x"), Some("ERROR"));
+}
+
+#[test]
+fn timestamp_finder_value() {
+    let date_finder = TimestampFinder::new("clf").unwrap();
+    assert_eq!(date_finder.value("0/0/0/78/78 206"), None);
+
+    let date_finder = date_finder.with_value(Regex::new(r"(?:\d+/){4}(\d+)").unwrap());
+    assert_eq!(date_finder.value("0/0/0/78/78 206"), Some(78.0));
+    assert_eq!(date_finder.value("-1/-1/-1/-1/0 503"), None);
+
+    let date_finder = date_finder.with_value(Regex::new(r"took (-?\d+(?:\.\d+)?)ms").unwrap());
+    assert_eq!(date_finder.value("took 12.5ms"), Some(12.5));
+    assert_eq!(date_finder.value("took -3ms"), Some(-3.0));
 }
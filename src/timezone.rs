@@ -0,0 +1,162 @@
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime, Offset, TimeDelta, TimeZone, Utc};
+use chrono_tz::Tz;
+use std::str::FromStr;
+
+/// The timezone used to interpret timestamps that don't carry their own UTC offset.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Timezone {
+    #[default]
+    Utc,
+    Local,
+    Fixed(FixedOffset),
+    Named(Tz),
+}
+
+impl Timezone {
+    /// Resolves a timezone name as it appears in a log line (`%Z`). Besides IANA names, this
+    /// accepts the abbreviations defined by RFC 2822, which are the only ones that are unambiguous.
+    pub fn from_name(name: &str) -> Option<Self> {
+        let hours = match name {
+            "UTC" | "UT" | "GMT" | "Z" => return Some(Timezone::Utc),
+            "EDT" => -4,
+            "EST" | "CDT" => -5,
+            "CST" | "MDT" => -6,
+            "MST" | "PDT" => -7,
+            "PST" => -8,
+            _ => return name.parse::<Tz>().ok().map(Timezone::Named),
+        };
+        FixedOffset::east_opt(hours * 3600).map(Timezone::Fixed)
+    }
+
+    /// Converts a date and time on the wall clock in this timezone to UTC.
+    ///
+    /// When the clocks go back, an ambiguous time resolves to the earlier of the two instants. When
+    /// they go forward, a time that falls into the gap is interpreted with the offset from before
+    /// the change.
+    pub fn to_utc(&self, datetime: &NaiveDateTime) -> DateTime<Utc> {
+        match self {
+            Timezone::Utc => datetime.and_utc(),
+            Timezone::Local => resolve_local(&Local, datetime),
+            Timezone::Fixed(offset) => resolve_local(offset, datetime),
+            Timezone::Named(tz) => resolve_local(tz, datetime),
+        }
+    }
+
+    /// Finds how far ahead of UTC the wall clock in this timezone is at `timestamp`, in
+    /// nanoseconds.
+    pub fn utc_offset(&self, timestamp: i64) -> i64 {
+        let datetime = DateTime::from_timestamp_nanos(timestamp).naive_utc();
+        let seconds = match self {
+            Timezone::Utc => 0,
+            Timezone::Local => Local.offset_from_utc_datetime(&datetime).local_minus_utc(),
+            Timezone::Fixed(offset) => offset.local_minus_utc(),
+            Timezone::Named(tz) => tz
+                .offset_from_utc_datetime(&datetime)
+                .fix()
+                .local_minus_utc(),
+        };
+        i64::from(seconds) * 1_000_000_000
+    }
+}
+
+fn resolve_local<Z: TimeZone>(tz: &Z, datetime: &NaiveDateTime) -> DateTime<Utc> {
+    if let Some(resolved) = tz.from_local_datetime(datetime).earliest() {
+        return resolved.with_timezone(&Utc);
+    }
+
+    // Transitions are never less than a day apart, so the offset from a day earlier is the one
+    // that was in effect before the gap.
+    let day_before = *datetime - TimeDelta::days(1);
+    match tz.offset_from_local_datetime(&day_before).earliest() {
+        Some(offset) => (*datetime - offset.fix()).and_utc(),
+        None => datetime.and_utc(),
+    }
+}
+
+impl FromStr for Timezone {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "UTC" | "utc" | "Z" => return Ok(Timezone::Utc),
+            "local" => return Ok(Timezone::Local),
+            _ => {}
+        }
+
+        if s.starts_with(['+', '-']) {
+            return FixedOffset::from_str(s)
+                .map(Timezone::Fixed)
+                .map_err(|_| anyhow!("invalid UTC offset '{}' (expected e.g. '+02:00')", s));
+        }
+
+        s.parse::<Tz>().map(Timezone::Named).map_err(|_| {
+            anyhow!(
+                "unknown timezone '{}' (expected an IANA name like 'Europe/Berlin', an offset like '+02:00', 'UTC' or 'local')",
+                s
+            )
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn naive(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap()
+    }
+
+    #[test]
+    fn timezone_from_str() {
+        assert_eq!("UTC".parse::<Timezone>().unwrap(), Timezone::Utc);
+        assert_eq!("local".parse::<Timezone>().unwrap(), Timezone::Local);
+        assert_eq!(
+            "+05:30".parse::<Timezone>().unwrap(),
+            Timezone::Fixed(FixedOffset::east_opt(5 * 3600 + 30 * 60).unwrap())
+        );
+        assert_eq!(
+            "-0800".parse::<Timezone>().unwrap(),
+            Timezone::Fixed(FixedOffset::west_opt(8 * 3600).unwrap())
+        );
+        assert_eq!(
+            "America/New_York".parse::<Timezone>().unwrap(),
+            Timezone::Named(Tz::America__New_York)
+        );
+        assert!("Mars/Olympus_Mons".parse::<Timezone>().is_err());
+        assert!("+25:00".parse::<Timezone>().is_err());
+    }
+
+    #[test]
+    fn timezone_from_name() {
+        assert_eq!(Timezone::from_name("GMT"), Some(Timezone::Utc));
+        assert_eq!(
+            Timezone::from_name("PDT"),
+            Some(Timezone::Fixed(FixedOffset::west_opt(7 * 3600).unwrap()))
+        );
+        assert_eq!(
+            Timezone::from_name("Europe/Berlin"),
+            Some(Timezone::Named(Tz::Europe__Berlin))
+        );
+        assert_eq!(Timezone::from_name("IST"), None);
+    }
+
+    #[test]
+    fn timezone_to_utc_across_dst() {
+        let new_york = Timezone::Named(Tz::America__New_York);
+
+        // Standard time (UTC-5) and daylight saving time (UTC-4)
+        let winter = new_york.to_utc(&naive("2024-01-15 12:00:00"));
+        assert_eq!(winter.timestamp(), 1705338000);
+        let summer = new_york.to_utc(&naive("2024-07-15 12:00:00"));
+        assert_eq!(summer.timestamp(), 1721059200);
+
+        // 01:30 happens twice on 2024-11-03; the first one is still daylight saving time
+        let ambiguous = new_york.to_utc(&naive("2024-11-03 01:30:00"));
+        assert_eq!(ambiguous.timestamp(), 1730611800);
+
+        // 02:30 doesn't exist on 2024-03-10; it's read with the standard time offset
+        let skipped = new_york.to_utc(&naive("2024-03-10 02:30:00"));
+        assert_eq!(skipped.timestamp(), 1710055800);
+    }
+}
@@ -0,0 +1,310 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn krapslog(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_krapslog"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to run krapslog");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+fn log_file(contents: &str) -> tempfile::NamedTempFile {
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(contents.as_bytes()).unwrap();
+    file
+}
+
+#[test]
+fn year_rolls_back_across_new_year() {
+    let log = log_file(
+        "Dec 30 23:00:00 host cron[1]: one
+Dec 31 23:59:59 host cron[1]: two
+Jan  1 00:00:01 host cron[1]: three
+Jan  2 12:00:00 host cron[1]: four
+",
+    );
+    let path = log.path().to_str().unwrap();
+    let output = krapslog(
+        &["--format", "syslog", "--year", "2024", "--stats", path],
+        "",
+    );
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("First timestamp:        2023-12-30 23:00:00 UTC"));
+    assert!(stderr.contains("Last timestamp:         2024-01-02 12:00:00 UTC"));
+    assert!(stderr.contains("Time span:              2d 13h 0m 0s"));
+}
+
+#[test]
+fn logfmt_key_needs_an_equals_sign() {
+    let log = log_file(
+        "level=info when=2024-01-02T03:04:05Z msg=a
+level=info when=2024-01-02T04:04:05Z msg=b
+",
+    );
+    let path = log.path().to_str().unwrap();
+
+    let output = krapslog(&["--logfmt=when", "--stats", path], "");
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("With a timestamp:       2 (100.0%)"));
+
+    // Without one, the key is taken for a file
+    let output = krapslog(&["--logfmt", "when", path], "");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Couldn't open when"));
+}
+
+#[test]
+fn format_auto_only_once() {
+    let output = krapslog(&["-F", "auto", "-F", "clf", "-F", "auto"], "");
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--format auto can only be given once"));
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+const CLF_LOG: &str = "[23/Nov/2019:06:26:40 +0000] GET /
+[23/Nov/2019:06:26:41 +0000] GET /
+[23/Nov/2019:06:26:42 +0000] GET /
+";
+
+#[test]
+fn compressed_stdin_arrives_in_pieces() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_krapslog"))
+        .args(["--stats"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    // The first read sees less than the gzip magic number
+    let data = gzip(CLF_LOG.as_bytes());
+    let mut stdin = child.stdin.take().unwrap();
+    stdin.write_all(&data[..1]).unwrap();
+    stdin.flush().unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(100));
+    stdin.write_all(&data[1..]).unwrap();
+    drop(stdin);
+
+    let output = child.wait_with_output().unwrap();
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("With a timestamp:       3 (100.0%)"));
+}
+
+#[test]
+fn corrupt_compressed_log_fails() {
+    let mut data = gzip(CLF_LOG.repeat(100).as_bytes());
+    let middle = data.len() / 2;
+    data[middle..middle + 8].fill(0xff);
+
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(&data).unwrap();
+    let output = krapslog(&[file.path().to_str().unwrap()], "");
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Couldn't read"));
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_krapslog"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(&data).unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert!(!output.status.success());
+}
+
+/// A log whose lines cross the boundaries between gzip members, including one line that's longer
+/// than the members that a thread is given, with its timestamp at the end.
+fn log_across_members() -> String {
+    let mut log = CLF_LOG.repeat(10);
+    log.push_str(&"x".repeat(3000));
+    log.push_str(" [23/Nov/2019:06:26:43 +0000] GET /\n");
+    log.push_str(&CLF_LOG.repeat(10));
+    log
+}
+
+#[test]
+fn lines_cross_bgzf_blocks() {
+    // The same log as log_across_members(), in BGZF blocks of 40 bytes each
+    let path = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/long_line_bgzf.log.gz"
+    );
+    let output = krapslog(&["--stats", "--concurrency", "8", path], "");
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Lines:                  61"));
+    assert!(stderr.contains("With a timestamp:       61 (100.0%)"));
+}
+
+#[test]
+fn lines_cross_gzip_members() {
+    let log = log_across_members();
+    let data: Vec<u8> = log.as_bytes().chunks(40).flat_map(gzip).collect();
+    let mut file = tempfile::NamedTempFile::new().unwrap();
+    file.write_all(&data).unwrap();
+
+    let output = krapslog(&["--stats", file.path().to_str().unwrap()], "");
+    assert!(output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("Lines:                  61"));
+    assert!(stderr.contains("With a timestamp:       61 (100.0%)"));
+}
+
+#[test]
+fn files_are_merged_in_time_order() {
+    let later = log_file(
+        "[23/Nov/2019:08:00:00 +0000] GET /
+[23/Nov/2019:09:00:00 +0000] GET /
+",
+    );
+    let earlier = log_file(
+        "[23/Nov/2019:06:00:00 +0000] GET /
+[23/Nov/2019:07:00:00 +0000] GET /
+",
+    );
+    let output = krapslog(
+        &[
+            "--markers",
+            "4",
+            later.path().to_str().unwrap(),
+            earlier.path().to_str().unwrap(),
+        ],
+        "",
+    );
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(lines.first().unwrap().ends_with("2019-11-23 09:00:00"));
+    assert!(lines.last().unwrap().starts_with("2019-11-23 06:00:00"));
+}
+
+#[test]
+fn labels_with_non_ascii_names_are_padded_by_characters() {
+    let dir = tempfile::tempdir().unwrap();
+    let mut paths = vec![];
+    for (name, contents) in [
+        (
+            "zoë.log",
+            "[23/Nov/2019:06:00:00 +0000] GET /\n[23/Nov/2019:08:00:00 +0000] GET /\n",
+        ),
+        ("zoe.log", "[23/Nov/2019:07:00:00 +0000] GET /\n"),
+    ] {
+        let path = dir.path().join(name);
+        std::fs::write(&path, contents).unwrap();
+        paths.push(path.to_str().unwrap().to_string());
+    }
+    let output = krapslog(&["--per-file", &paths[0], &paths[1]], "");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let starts: Vec<usize> = stdout
+        .lines()
+        .filter_map(|line| line.chars().position(|c| c == '▁' || c == '█'))
+        .collect();
+    // The name, the count and a space after each
+    let label_width = paths[0].chars().count() + 3;
+    assert_eq!(starts, [label_width, label_width]);
+}
+
+#[test]
+fn follow_window_ends_at_the_latest_timestamp() {
+    let log = log_file(
+        "[23/Nov/2019:06:00:00 +0000] GET /
+[23/Nov/2019:07:30:00 +0000] GET /
+[23/Nov/2019:08:00:00 +0000] GET /
+",
+    );
+    let mut child = Command::new(env!("CARGO_BIN_EXE_krapslog"))
+        .args(["--follow", "--window", "1h", "--interval", "100ms"])
+        .arg(log.path())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    std::thread::sleep(std::time::Duration::from_millis(500));
+    child.kill().unwrap();
+    let output = child.wait_with_output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Last 1h up to 2019-11-23 08:00:00: 2 lines"));
+}
+
+#[test]
+fn labelled_views_mark_timestamps_like_the_plain_one() {
+    let log = "[23/Nov/2019:06:00:00 +0000] GET / 200
+[23/Nov/2019:07:00:00 +0000] GET / 503
+[23/Nov/2019:08:00:00 +0000] GET / 200
+";
+    for args in [
+        &["--markers", "4"][..],
+        &["--markers", "4", "--group-by", r" (\d)\d\d$"],
+    ] {
+        let output = krapslog(args, log);
+        assert!(output.status.success());
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let markers: Vec<&str> = stdout
+            .split_whitespace()
+            .filter(|word| word.contains(':'))
+            .collect();
+        assert_eq!(markers.len(), 4);
+        assert!(markers
+            .iter()
+            .all(|marker| ["06:00:00", "07:00:00", "08:00:00"].contains(marker)));
+    }
+}
+
+#[test]
+fn markers_show_the_wall_clock_of_the_timezone() {
+    let log = "2024-01-02 03:04:05 one
+2024-01-02 06:04:05 two
+2024-01-02 09:04:05 three
+";
+    let output = krapslog(
+        &[
+            "--format",
+            "auto",
+            "--timezone",
+            "Europe/Berlin",
+            "--bucket",
+            "5m",
+            "--markers",
+            "3",
+        ],
+        log,
+    );
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("2024-01-02 03:00:00"));
+    assert!(!stdout.contains("2024-01-02 02:"));
+}
+
+#[test]
+fn two_markers_label_both_edges() {
+    let log = "[23/Nov/2019:06:00:00 +0000] GET /
+[23/Nov/2019:07:00:00 +0000] GET /
+[23/Nov/2019:08:00:00 +0000] GET /
+";
+    let output = krapslog(&["--markers", "2"], log);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("2019-11-23 06:00:00"));
+    assert!(stdout.contains("2019-11-23 08:00:00"));
+}